@@ -5,6 +5,11 @@ pub mod human;
 pub mod minimax;
 pub mod alphabeta;
 pub mod mcts;
+pub mod rollout;
+pub mod parallel_mcts;
+pub mod pvs;
+pub mod endgame;
+pub mod search;
 
 pub trait Agent {
     fn next_action_option(&self, board: &Board) -> Option<Action>;
@@ -14,7 +19,7 @@ pub trait Agent {
     }
 
     fn get_action_from(&self, state: State) -> Action where Self: Sized{
-        let mut tmp_board = Board::init(self, self);
+        let mut tmp_board = Board::init();
         tmp_board.set_state(state);
         self.next_action(&tmp_board)
     }
@@ -0,0 +1,255 @@
+use std::time::Instant;
+
+use super::Agent;
+use crate::components::{action::Action, board::Board, bitboard::{BitBoard, BitBoardTrait}, constants::{ScoreType, TOP_BIT, BOARD_SIZE}, state::State};
+
+const QUADRANT_MASKS: [BitBoard; 4] = [
+    0xf0f0f0f000000000,
+    0x0f0f0f0f00000000,
+    0x00000000f0f0f0f0,
+    0x000000000f0f0f0f,
+];
+const FASTEST_FIRST_EMPTIES: i32 = 7;   // 空きマスがこれより多いときは相手の着手可能数で並べる
+const MAX_DISC_DIFF: ScoreType = BOARD_SIZE as ScoreType;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SolveMode {
+    WinLossDraw,    // 勝敗だけを求める. scoreは1, 0, -1
+    Exact,          // 最終石差を求める
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SolveResult {
+    pub action: Action,
+    pub score:  ScoreType,  // 手番側から見た値
+    pub nodes:  u64,
+}
+
+struct SolveContext {
+    now:    Instant,
+    nodes:  u64,
+}
+
+// 空きマスがempty_threshold以下になったら終局まで読み切る
+pub struct EndgameSolver {
+    pub empty_threshold:    i32,
+    pub mode:   SolveMode,
+    pub time_limit: u128,   // micro sec
+}
+
+impl EndgameSolver {
+    pub fn new(empty_threshold: i32, mode: SolveMode, time_limit: u128) -> EndgameSolver {
+        EndgameSolver {
+            empty_threshold,
+            mode,
+            time_limit,
+        }
+    }
+
+    pub fn empty_num(state: &State) -> i32 {
+        (!(state.player_bit | state.opponent_bit)).count()
+    }
+
+    pub fn is_applicable(&self, board: &Board) -> bool {
+        EndgameSolver::empty_num(&board.state) <= self.empty_threshold
+    }
+
+    // 空きマスは勝った側のものとして数える
+    pub fn final_score(state: &State) -> ScoreType {
+        let player_cnt: ScoreType = state.player_bit.count();
+        let opponent_cnt: ScoreType = state.opponent_bit.count();
+        let empty_cnt: ScoreType = BOARD_SIZE as ScoreType - player_cnt - opponent_cnt;
+        if player_cnt > opponent_cnt {
+            player_cnt - opponent_cnt + empty_cnt
+        } else if player_cnt < opponent_cnt {
+            player_cnt - opponent_cnt - empty_cnt
+        } else {
+            0
+        }
+    }
+
+    // 相手の確定石は最後まで相手のものなので, 最終石差はMAX_DISC_DIFF - 2 * (相手の確定石数)以下になる
+    // 自分の確定石についても同様に下限が決まる. 上限がalpha以下か下限がbeta以上なら枝刈りできる
+    fn stability_cutoff(state: &State, alpha: ScoreType, beta: ScoreType) -> Option<ScoreType> {
+        // 確定石の計算は重いので, 石数から見て枝刈りの可能性があるときだけ行う
+        let upper_possible: bool = MAX_DISC_DIFF - 2 * state.opponent_bit.count() <= alpha;
+        let lower_possible: bool = 2 * state.player_bit.count() - MAX_DISC_DIFF >= beta;
+        if !upper_possible && !lower_possible {
+            return None;
+        }
+
+        let stable_bit: BitBoard = state.stable_bitboard();
+        let upper: ScoreType = MAX_DISC_DIFF - 2 * (stable_bit & state.opponent_bit).count();
+        if upper <= alpha {
+            return Some(upper);
+        }
+        let lower: ScoreType = 2 * (stable_bit & state.player_bit).count() - MAX_DISC_DIFF;
+        if lower >= beta {
+            return Some(lower);
+        }
+        None
+    }
+
+    // 適用範囲外, 合法手がない, 時間切れのいずれかの場合はNone
+    pub fn solve(&self, board: &Board) -> Option<SolveResult> {
+        if !self.is_applicable(board) {
+            return None;
+        }
+        let mut context: SolveContext = SolveContext {
+            now:    Instant::now(),
+            nodes:  0,
+        };
+        let (mut alpha, beta): (ScoreType, ScoreType) = match self.mode {
+            SolveMode::WinLossDraw => (-1, 1),
+            SolveMode::Exact => (-MAX_DISC_DIFF, MAX_DISC_DIFF),
+        };
+
+        let mut state: State = board.state;
+        let mut best: Option<(Action, ScoreType)> = None;
+        for action in self.ordered_actions(&state) {
+            let flips: BitBoard = state.flips(action);
+            state.make_move(action, flips);
+            let result: Option<ScoreType> = self.solve_score(&mut state, -beta, -alpha, false, &mut context);
+            state.undo_move(action, flips);
+            let score: ScoreType = -result?;
+            if best.is_none() || score > alpha {
+                alpha = alpha.max(score);
+                best = Some((action, score));
+            }
+            if score >= beta {
+                break;
+            }
+        }
+
+        best.map(|(action, score)| SolveResult {
+            action,
+            score:  if self.mode == SolveMode::WinLossDraw { score.signum() } else { score },
+            nodes:  context.nodes,
+        })
+    }
+
+    fn solve_score(&self, state: &mut State, mut alpha: ScoreType, beta: ScoreType, passed: bool, context: &mut SolveContext) -> Option<ScoreType> {
+        if self.time_limit < context.now.elapsed().as_micros() {
+            return None;
+        }
+        context.nodes += 1;
+
+        if let Some(score) = EndgameSolver::stability_cutoff(state, alpha, beta) {
+            return Some(score);
+        }
+
+        let actions: Vec<Action> = self.ordered_actions(state);
+        if actions.is_empty() {
+            if passed {
+                return Some(EndgameSolver::final_score(state));
+            }
+            state.make_pass();
+            let result: Option<ScoreType> = self.solve_score(state, -beta, -alpha, true, context);
+            state.make_pass();
+            return result.map(|score| -score);
+        }
+
+        let mut best_score: ScoreType = -MAX_DISC_DIFF - 1;
+        for action in actions {
+            let flips: BitBoard = state.flips(action);
+            state.make_move(action, flips);
+            let result: Option<ScoreType> = self.solve_score(state, -beta, -alpha, false, context);
+            state.undo_move(action, flips);
+            let score: ScoreType = -result?;
+            if score > best_score {
+                best_score = score;
+            }
+            if score > alpha {
+                alpha = score;
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+        Some(best_score)
+    }
+
+    // 空きマスが奇数の象限の手を優先する(偶数理論). 空きマスが多いときは相手の着手可能数が少ない手を優先する
+    fn ordered_actions(&self, state: &State) -> Vec<Action> {
+        let legal_bitboard: BitBoard = state.legal_actions_bitboard();
+        let empty_bitboard: BitBoard = !(state.player_bit | state.opponent_bit);
+        let fastest_first: bool = empty_bitboard.count() > FASTEST_FIRST_EMPTIES;
+
+        let mut keyed_actions: Vec<(i32, Action)> = Vec::with_capacity(legal_bitboard.count() as usize);
+        let mut rest: BitBoard = legal_bitboard;
+        while rest != 0 {
+            let action: Action = Action::action_from_bitboard(TOP_BIT >> rest.leading_zeros());
+            rest &= !action.bitboard;
+
+            let quadrant: BitBoard = QUADRANT_MASKS.iter().copied().find(|mask| mask & action.bitboard != 0).unwrap();
+            let mut key: i32 = if (empty_bitboard & quadrant).count() % 2 == 1 { 0 } else { 1 };
+            if fastest_first {
+                let mut next_state: State = *state;
+                next_state.make_move(action, state.flips(action));
+                key += 2 * next_state.legal_actions_bitboard().count();
+            }
+            keyed_actions.push((key, action));
+        }
+        keyed_actions.sort_by_key(|&(key, _)| key);
+        keyed_actions.into_iter().map(|(_, action)| action).collect()
+    }
+}
+
+// 終盤は読み切り, それ以外はagentに任せる
+// agentのrest_timeにはsolverのtime_limit分を含めておく
+pub struct EndgameAgent<A: Agent> {
+    pub agent:  A,
+    pub solver: EndgameSolver,
+}
+
+impl<A: Agent> Agent for EndgameAgent<A> {
+    fn next_action_option(&self, board: &Board) -> Option<Action> {
+        match self.solver.solve(board) {
+            Some(result) => Some(result.action),
+            None => self.agent.next_action_option(board),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::test_utils::random_board;
+
+    // 枝刈りなしのnegamax
+    fn brute_force(board: Board, passed: bool) -> ScoreType {
+        let legal_actions: Vec<Action> = board.legal_actions();
+        if legal_actions.is_empty() {
+            if passed {
+                return EndgameSolver::final_score(&board.state);
+            }
+            return -brute_force(board.play_pass(), true);
+        }
+        legal_actions.iter().map(|&action| -brute_force(board.play_onestep(action), false)).max().unwrap()
+    }
+
+    #[test]
+    fn solve_matches_brute_force() {
+        let exact: EndgameSolver = EndgameSolver::new(8, SolveMode::Exact, u128::MAX);
+        let win_loss_draw: EndgameSolver = EndgameSolver::new(8, SolveMode::WinLossDraw, u128::MAX);
+        let mut rng: StdRng = StdRng::seed_from_u64(0);
+        let mut checked: usize = 0;
+        while checked < 20 {
+            let board: Board = random_board(52, &mut rng);
+            if board.legal_actions().is_empty() {
+                continue;
+            }
+
+            let expected: ScoreType = brute_force(board, false);
+            let result: SolveResult = exact.solve(&board).unwrap();
+            assert_eq!(result.score, expected);
+            assert_eq!(-brute_force(board.play_onestep(result.action), false), expected);
+            let result: SolveResult = win_loss_draw.solve(&board).unwrap();
+            assert_eq!(result.score, expected.signum());
+            assert_eq!(-brute_force(board.play_onestep(result.action), false).signum(), expected.signum());
+            checked += 1;
+        }
+    }
+}
@@ -1,41 +1,134 @@
-use std::time::Instant;
+use std::{cell::RefCell, time::Instant};
 
-use super::Agent;
-use crate::components::{action::Action, board::Board, enums::WinningStatus, constants::TIME_LIMT};
+use rand::{rngs::StdRng, SeedableRng};
 
+use super::{Agent, rollout::{RolloutPolicy, UniformRollout}};
+use crate::{components::{action::Action, board::Board, bitboard::BitBoard, enums::{BoardStatus, WinningStatus}, constants::{TIME_LIMT, BOARD_SIZE}, state::State}, evals::{cell_score::CellEval, EvalTrait}};
 
 
-type ValueType = f64;
+
+pub(super) type ValueType = f64;
 
 const INF_VALUE: ValueType = 10000.0;
+const REUSE_DEPTH: u32 = 2;
+const VIRTUAL_LOSS: ValueType = 1.;    // 親から見て負けの扱い
+
+// RAVEの重みbetaの決め方. betaが大きいほどAMAFの値を重視する
+#[derive(Debug, Clone, Copy)]
+pub enum RaveSchedule {
+    Equivalence(ValueType),    // beta = sqrt(k / (3n + k))
+    MinimumMse(ValueType),     // beta = m / (n + m + 4b^2 nm). 値はバイアスb
+}
+
+impl RaveSchedule {
+    fn beta(&self, try_count: u32, amaf_count: u32) -> ValueType {
+        let n: ValueType = try_count as ValueType;
+        let m: ValueType = amaf_count as ValueType;
+        match *self {
+            RaveSchedule::Equivalence(k) => (k / (3. * n + k)).sqrt(),
+            RaveSchedule::MinimumMse(bias) => m / (n + m + 4. * bias * bias * n * m),
+        }
+    }
+}
+
+// 子ノードの選び方
+#[derive(Debug, Clone, Copy)]
+pub enum TreePolicy {
+    Ucb1(ValueType),    // 探索定数C
+    Ucb1Tuned,          // 勝率の分散で探索項を絞る
+    Puct {              // 事前確率は着手後の評価値のsoftmax. temperatureが0以下なら最大の手だけ
+        c:  ValueType,
+        temperature:    ValueType,
+    },
+}
 
-struct Node<'a> {
-    pub board: Board<'a>,
+// 探索後にルートから手を選ぶ方法
+#[derive(Debug, Clone, Copy)]
+pub enum FinalSelection {
+    MaxVisits,
+    MaxValue,
+    RobustMax,              // 訪問回数と勝率の両方が最大の子. 一致しなければ訪問回数で選ぶ
+    SecureChild(ValueType), // 勝率 - A / sqrt(訪問回数) が最大の子. 値はA
+}
+
+// ログ用のルートの子ノードの統計
+#[derive(Debug, Clone, Copy)]
+pub struct RootStat {
+    pub action: Option<Action>,
+    pub try_count:  u32,
+    pub win_rate:   ValueType,  // ルートの手番側から見た値. 未訪問なら0
+    pub prior:  ValueType,
+}
+
+pub(super) struct SearchConfig<'a> {
+    pub expand_threshold:   u32,
+    pub rollout_policy: &'a dyn RolloutPolicy,
+    pub tree_policy:    TreePolicy,
+    pub rave:   Option<RaveSchedule>,
+    pub evaluator:  &'a dyn EvalTrait,
+}
+
+pub(super) struct Node {
+    pub board: Board,
+    pub action: Option<Action>,  // 親からこのノードへの手. パスはNone
     pub sum_w:  ValueType,
-    pub child_nodes:    Vec<Node<'a>>,
+    pub sum_w2: ValueType,  // 結果の2乗和. UCB1-Tunedの分散に使う
+    pub child_nodes:    Vec<Node>,
     pub try_count:  u32,
+    pub prior:  ValueType,  // PUCTの事前確率
+    pub amaf_w: ValueType,  // All-Moves-As-First. 親の手番側が以降のどこかでactionを打ったシミュレーションの結果
+    pub amaf_count: u32,
 }
 
-impl<'a> Node<'a> {
-    pub fn init(board: Board<'a>) -> Node<'a> {
+impl Node {
+    pub fn init(board: Board, action: Option<Action>) -> Node {
         Node {
             board,
+            action,
             sum_w:  0.,
+            sum_w2: 0.,
             child_nodes:    vec![],
             try_count:  0,
+            prior:  1.,
+            amaf_w: 0.,
+            amaf_count: 0,
         }
     }
 
-    pub fn expand(& mut self) {
+    pub fn expand(& mut self, config: &SearchConfig) {
+        if self.board.status() == BoardStatus::Pass {
+            let add_node: Node = Node::init(self.board.play_pass(), None);
+            self.child_nodes.push(add_node);
+            return;
+        }
         let actions: Vec<Action> = self.board.legal_actions();
         for action in actions {
             let new_board = self.board.play_onestep(action);
-            let add_node: Node = Node::init(new_board);
+            let add_node: Node = Node::init(new_board, Some(action));
             self.child_nodes.push(add_node);
         }
+        if let TreePolicy::Puct { temperature, .. } = config.tree_policy {
+            self.set_priors(config.evaluator, temperature);
+        }
+    }
+
+    fn set_priors(&mut self, evaluator: &dyn EvalTrait, temperature: ValueType) {
+        let scores: Vec<ValueType> = self.child_nodes.iter()
+            .map(|node| -evaluator.eval(&node.board) as ValueType)
+            .collect();
+        let max_score: ValueType = scores.iter().copied().fold(-INF_VALUE, ValueType::max);
+        // temperatureが0以下なら評価値が最大の子で等分する
+        let weights: Vec<ValueType> = scores.iter()
+            .map(|&score| if temperature <= 0. { if score == max_score { 1. } else { 0. } } else { ((score - max_score) / temperature).exp() })
+            .collect();
+        let sum_weight: ValueType = weights.iter().sum();
+        for (node, weight) in self.child_nodes.iter_mut().zip(weights) {
+            node.prior = weight / sum_weight;
+        }
     }
 
-    pub fn evaluate(&mut self, expand_threshold: u32) -> ValueType {
+    // movesにはこのノード以降に打たれた手を追加する(パスは0)
+    pub fn evaluate(&mut self, config: &SearchConfig, rng: &mut StdRng, moves: &mut Vec<BitBoard>) -> ValueType {
         let winning_status = self.board.winning_status();
         if winning_status != WinningStatus::NotFinished {
             let value:ValueType = if winning_status == WinningStatus::Win {
@@ -49,38 +142,155 @@ impl<'a> Node<'a> {
             self.try_count += 1;
             value
         } else if self.child_nodes.is_empty() {
-            let value = self.get_playout_score();
+            let value = self.get_playout_score(config.rollout_policy, rng, moves);
             self.sum_w += value;
+            self.sum_w2 += value * value;
             self.try_count += 1;
-            if self.try_count == expand_threshold {
-                self.expand();
+            if self.try_count == config.expand_threshold {
+                self.expand(config);
             }
             value
         } else {
-            let index = self.next_child_node_index();
+            let index = self.next_child_node_index(config);
+            let start = moves.len();
             let node = &mut self.child_nodes[index];
-            let value = 1. - node.evaluate(expand_threshold);
+            moves.push(node.action.map_or(0, |action| action.bitboard));
+            let value = 1. - node.evaluate(config, rng, moves);
             self.sum_w += value;
+            self.sum_w2 += value * value;
             self.try_count += 1;
+            if config.rave.is_some() {
+                self.update_amaf(&moves[start..], value);
+            }
             value
         }
     }
 
-    fn get_playout_score(&self) -> ValueType {
-        let winning_status = self.board.winning_status();
-        if winning_status == WinningStatus::Win {
-            1.
-        } else if winning_status == WinningStatus::Lose {
-            0.
-        } else {
-            0.5
+    // movesはこのノードから打たれた手. 手番側が打った手に対応する子のAMAFを更新する
+    fn update_amaf(&mut self, moves: &[BitBoard], value: ValueType) {
+        let own_moves: BitBoard = moves.iter().step_by(2).fold(0, |acc, &bitboard| acc | bitboard);
+        for node in self.child_nodes.iter_mut() {
+            if let Some(action) = node.action {
+                if action.bitboard & own_moves != 0 {
+                    node.amaf_w += 1. - value;
+                    node.amaf_count += 1;
+                }
+            }
+        }
+    }
+
+    fn get_playout_score(&self, policy: &dyn RolloutPolicy, rng: &mut StdRng, moves: &mut Vec<BitBoard>) -> ValueType {
+        playout_score(&self.board, policy, rng, moves)
+    }
+
+    // 木を共有する並列探索用. ルートから葉まで選び, 通過したノードに仮想敗北を加えて他スレッドの選択をずらす
+    pub(super) fn select_with_virtual_loss(&mut self, config: &SearchConfig) -> (Vec<usize>, Board) {
+        let mut path: Vec<usize> = vec![];
+        let mut node: &mut Node = self;
+        loop {
+            node.sum_w += VIRTUAL_LOSS;
+            node.try_count += 1;
+            if node.child_nodes.is_empty() {
+                break;
+            }
+            let index = node.next_child_node_index(config);
+            path.push(index);
+            node = &mut node.child_nodes[index];
+        }
+        (path, node.board)
+    }
+
+    // select_with_virtual_lossで選んだ経路の仮想敗北を葉の値valueで置き換える
+    pub(super) fn backpropagate(&mut self, path: &[usize], value: ValueType, config: &SearchConfig) {
+        let mut node: &mut Node = self;
+        for (depth, &index) in path.iter().enumerate() {
+            let node_value = if (path.len() - depth).is_multiple_of(2) { value } else { 1. - value };
+            node.sum_w += node_value - VIRTUAL_LOSS;
+            node.sum_w2 += node_value * node_value;
+            node = &mut node.child_nodes[index];
+        }
+        node.sum_w += value - VIRTUAL_LOSS;
+        node.sum_w2 += value * value;
+        if node.child_nodes.is_empty() && node.try_count >= config.expand_threshold && node.board.status() != BoardStatus::Finished {
+            node.expand(config);
+        }
+    }
+
+    // depth手以内の子孫からstateが一致するノードを取り出す. 残りの木は捨てる
+    fn take_descendant(self, state: &State, depth: u32) -> Option<Node> {
+        if self.board.state == *state {
+            return Some(self);
+        }
+        if depth == 0 {
+            return None;
+        }
+        self.child_nodes.into_iter()
+            .find_map(|node| node.take_descendant(state, depth - 1))
+    }
+
+    // 親の手番側から見た勝率. raveがあればAMAFの勝率と混ぜる
+    fn win_rate(&self, rave: Option<RaveSchedule>) -> ValueType {
+        let mut win_rate: ValueType = 1. - self.sum_w / self.try_count as ValueType;
+        if let Some(schedule) = rave {
+            if self.amaf_count > 0 {
+                let beta: ValueType = schedule.beta(self.try_count, self.amaf_count);
+                win_rate = (1. - beta) * win_rate + beta * (1. - self.amaf_w / self.amaf_count as ValueType);
+            }
+        }
+        win_rate
+    }
+
+    fn root_stats(&self) -> Vec<RootStat> {
+        self.child_nodes.iter()
+            .map(|node| RootStat {
+                action: node.action,
+                try_count:  node.try_count,
+                win_rate:   if node.try_count == 0 { 0. } else { node.win_rate(None) },
+                prior:  node.prior,
+            })
+            .collect()
+    }
+
+    // どの子も訪問していなければ着手後の静的評価で選ぶ
+    pub(super) fn final_child_index(&self, selection: FinalSelection, evaluator: &dyn EvalTrait) -> Option<usize> {
+        if self.child_nodes.iter().all(|node| node.try_count == 0) {
+            return (0..self.child_nodes.len())
+                .max_by_key(|&i| -evaluator.eval(&self.child_nodes[i].board));
+        }
+
+        let visited_indices = || (0..self.child_nodes.len()).filter(|&i| self.child_nodes[i].try_count > 0);
+        let max_visits_index: Option<usize> = visited_indices()
+            .max_by_key(|&i| self.child_nodes[i].try_count);
+        let max_value_index: Option<usize> = visited_indices()
+            .max_by(|&i, &j| self.child_nodes[i].win_rate(None).total_cmp(&self.child_nodes[j].win_rate(None)));
+        match selection {
+            FinalSelection::MaxVisits => max_visits_index,
+            FinalSelection::MaxValue => max_value_index,
+            FinalSelection::RobustMax => {
+                let (visits_index, value_index) = (max_visits_index?, max_value_index?);
+                if self.child_nodes[visits_index].win_rate(None) >= self.child_nodes[value_index].win_rate(None) {
+                    Some(visits_index)
+                } else if self.child_nodes[value_index].try_count >= self.child_nodes[visits_index].try_count {
+                    Some(value_index)
+                } else {
+                    Some(visits_index)
+                }
+            },
+            FinalSelection::SecureChild(a) => {
+                let lower_bound = |i: usize| {
+                    let node: &Node = &self.child_nodes[i];
+                    node.win_rate(None) - a / (node.try_count as ValueType).sqrt()
+                };
+                visited_indices().max_by(|&i, &j| lower_bound(i).total_cmp(&lower_bound(j)))
+            },
         }
     }
 
-    fn next_child_node_index(&self) -> usize {
+    pub(super) fn next_child_node_index(&self, config: &SearchConfig) -> usize {
+        let visit_unvisited_first: bool = !matches!(config.tree_policy, TreePolicy::Puct { .. });
         let mut t = 0;
         for (i, node) in self.child_nodes.iter().enumerate() {
-            if node.try_count == 0 {
+            if node.try_count == 0 && visit_unvisited_first {
                 return i;
             }
             t += node.try_count;
@@ -88,43 +298,447 @@ impl<'a> Node<'a> {
         let mut best_value = -INF_VALUE;
         let mut best_index = 0;
 
+        let log_t: ValueType = (t as ValueType).ln();
         for (i, node) in self.child_nodes.iter().enumerate() {
-            let ucb1_value: f64 = 1. - node.sum_w / node.try_count as ValueType
-                                + 1. * (2. * (t as ValueType).ln() / node.try_count as ValueType).sqrt();
-            if ucb1_value > best_value {
+            let n: ValueType = node.try_count as ValueType;
+            let value: ValueType = match config.tree_policy {
+                TreePolicy::Ucb1(c) => {
+                    node.win_rate(config.rave) + c * (2. * log_t / n).sqrt()
+                },
+                TreePolicy::Ucb1Tuned => {
+                    let mean: ValueType = node.sum_w / n;
+                    let variance: ValueType = (node.sum_w2 / n - mean * mean).max(0.);
+                    let bound: ValueType = variance + (2. * log_t / n).sqrt();
+                    node.win_rate(config.rave) + (log_t / n * bound.min(0.25)).sqrt()
+                },
+                TreePolicy::Puct { c, .. } => {
+                    let win_rate: ValueType = if node.try_count == 0 { 0.5 } else { node.win_rate(config.rave) };
+                    win_rate + c * node.prior * (t as ValueType).sqrt() / (1. + n)
+                },
+            };
+            if value > best_value {
                 best_index = i;
-                best_value = ucb1_value;
+                best_value = value;
             }
         }
         best_index
     }
 }
 
+// 終局までpolicyで打ち進め, boardの手番側から見た勝ち1, 負け0, 引き分け0.5を返す
+// 打った手はmovesに追加する(パスは0)
+pub(super) fn playout_score(board: &Board, policy: &dyn RolloutPolicy, rng: &mut StdRng, moves: &mut Vec<BitBoard>) -> ValueType {
+    let mut tmp_board: Board = *board;
+    loop {
+        match tmp_board.status() {
+            BoardStatus::Finished => { break; },
+            BoardStatus::Pass => {
+                tmp_board.make_pass();
+                moves.push(0);
+            },
+            BoardStatus::Usual => {
+                let actions: Vec<Action> = tmp_board.legal_actions();
+                let action: Action = policy.choose(&tmp_board, &actions, rng);
+                let flips: BitBoard = tmp_board.state.flips(action);
+                tmp_board.make_move(action, flips);
+                moves.push(action.bitboard);
+            },
+        }
+    }
+
+    let value: ValueType = match tmp_board.winning_status() {
+        WinningStatus::Win => 1.,
+        WinningStatus::Lose => 0.,
+        _ => 0.5,
+    };
+    if tmp_board.turn() == board.turn() {
+        value
+    } else {
+        1. - value
+    }
+}
+
+#[allow(clippy::upper_case_acronyms)]
 pub struct MCTS {
     pub expand_threshold:  u32,
     pub rest_time: u128, // micro sec
+    pub rollout_policy: Box<dyn RolloutPolicy>,
+    pub tree_policy:    TreePolicy,
+    pub rave:   Option<RaveSchedule>,   // Noneならtree_policyの値のみ
+    pub final_selection:    FinalSelection,
+    pub evaluator:  Box<dyn EvalTrait>,  // PUCTの事前確率と未訪問時の手選びに使う
+    pub reuse_tree: bool,
+    rng:    RefCell<StdRng>,
+    root_stats: RefCell<Vec<RootStat>>, // 直前の探索のルートの統計
+    tree:   RefCell<Option<Node>>,  // 前回の探索木. 次の局面は自分の手と相手の応手の先にある
+}
+
+impl MCTS {
+    pub fn new(expand_threshold: u32, rest_time: u128) -> MCTS {
+        MCTS::with_tree_policy(expand_threshold, rest_time, TreePolicy::Ucb1(1.))
+    }
+
+    pub fn with_tree_policy(expand_threshold: u32, rest_time: u128, tree_policy: TreePolicy) -> MCTS {
+        MCTS {
+            expand_threshold,
+            rest_time,
+            rollout_policy: Box::new(UniformRollout),
+            tree_policy,
+            rave:   None,
+            final_selection:    FinalSelection::MaxVisits,
+            evaluator:  Box::new(CellEval::default()),
+            reuse_tree: true,
+            rng:    RefCell::new(StdRng::from_entropy()),
+            root_stats: RefCell::new(vec![]),
+            tree:   RefCell::new(None),
+        }
+    }
+
+    pub fn root_stats(&self) -> Vec<RootStat> {
+        self.root_stats.borrow().clone()
+    }
+
+    pub fn reset_tree(&self) {
+        self.tree.replace(None);
+    }
+
+    fn search_config(&self) -> SearchConfig<'_> {
+        SearchConfig {
+            expand_threshold:   self.expand_threshold,
+            rollout_policy: self.rollout_policy.as_ref(),
+            tree_policy:    self.tree_policy,
+            rave:   self.rave,
+            evaluator:  self.evaluator.as_ref(),
+        }
+    }
+
+    // 前回の木からboardに一致する部分木を探し, なければ新しく作る
+    fn take_root_node(&self, board: &Board, config: &SearchConfig) -> Node {
+        let old_root: Option<Node> = self.tree.take();
+        let reused_node: Option<Node> = if self.reuse_tree {
+            old_root.and_then(|node| node.take_descendant(&board.state, REUSE_DEPTH))
+        } else {
+            None
+        };
+        let mut root_node: Node = reused_node.unwrap_or_else(|| Node::init(*board, None));
+        if root_node.child_nodes.is_empty() {
+            root_node.expand(config);
+        }
+        root_node
+    }
+
+    // 同じseedからは同じプレイアウト列になる
+    pub fn set_seed(&self, seed: u64) {
+        self.rng.replace(StdRng::seed_from_u64(seed));
+    }
 }
 
 impl Agent for MCTS {
     fn next_action_option(&self, board: &Board) -> Option<Action> {
         let now = Instant::now();
-        let mut root_node: Node = Node::init(*board);
-        root_node.expand();
+        let rng = &mut self.rng.borrow_mut();
+        let config: SearchConfig = self.search_config();
+        let mut root_node: Node = self.take_root_node(board, &config);
+        let mut moves: Vec<BitBoard> = Vec::with_capacity(BOARD_SIZE);
         while now.elapsed().as_micros() + self.rest_time < TIME_LIMT {
-            root_node.evaluate(self.expand_threshold);
+            moves.clear();
+            root_node.evaluate(&config, rng, &mut moves);
+        }
+        let res_action: Option<Action> = root_node.final_child_index(self.final_selection, self.evaluator.as_ref())
+            .and_then(|index| root_node.child_nodes[index].action);
+        self.root_stats.replace(root_node.root_stats());
+
+        if self.reuse_tree {
+            self.tree.replace(Some(root_node));
+        }
+        res_action
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{components::constants::{ScoreType, TOP_BIT}, test_utils::random_board};
+
+    const A1: u64 = TOP_BIT;
+    const B1: u64 = TOP_BIT >> 1;
+    const A2: u64 = TOP_BIT >> 8;
+    const B2: u64 = TOP_BIT >> 9;
+
+    fn board_from(player_bit: u64, opponent_bit: u64) -> Board {
+        let mut board: Board = Board::init();
+        board.set_state(State::build(player_bit, opponent_bit));
+        board
+    }
+
+    fn seeded_mcts() -> MCTS {
+        let mcts: MCTS = MCTS::new(2, TIME_LIMT - 5000);
+        mcts.set_seed(0);
+        mcts
+    }
+
+    // 手番側(b1)は打てず, 相手(a1)はc1に打てる
+    #[test]
+    fn expand_adds_pass_child() {
+        let mut node: Node = Node::init(board_from(B1, A1), None);
+        node.expand(&MCTS::new(1, 0).search_config());
+        assert_eq!(node.child_nodes.len(), 1);
+        assert!(node.child_nodes[0].action.is_none());
+        assert_eq!(node.child_nodes[0].board.state, State::build(A1, B1));
+    }
+
+    #[test]
+    fn evaluate_searches_through_pass_node() {
+        let mut node: Node = Node::init(board_from(B1, A1), None);
+        let mut rng: StdRng = StdRng::seed_from_u64(0);
+        let config: SearchConfig = SearchConfig {
+            expand_threshold:   1,
+            rollout_policy: &UniformRollout,
+            tree_policy:    TreePolicy::Ucb1(1.),
+            rave:   None,
+            evaluator:  &CellEval::default(),
+        };
+        for _ in 0..20 {
+            node.evaluate(&config, &mut rng, &mut vec![]);
         }
-        let actions = board.legal_actions();
-        let mut res_action = None;
+        assert_eq!(node.try_count, 20);
+        assert_eq!(node.sum_w, 0.);
+        let pass_node: &Node = &node.child_nodes[0];
+        assert!(pass_node.action.is_none());
+        assert_eq!(pass_node.try_count, 19);    // 1回目は展開前のプレイアウト
+        assert!(!pass_node.child_nodes.is_empty());
+    }
 
-        let mut most_try_count = 0;
-        for i in 0..actions.len() {
-            let count = root_node.child_nodes[i].try_count;
-            if count > most_try_count {
-                most_try_count = count;
-                res_action = Some(actions[i]);
+    #[test]
+    fn root_pass_returns_none() {
+        assert!(seeded_mcts().next_action_option(&board_from(B1, A1)).is_none());
+    }
+
+    // どの手を打っても相手はパスになる局面
+    #[test]
+    fn opponent_pass_is_searched() {
+        let board: Board = board_from(A1 | A2, B1 | B2);
+        let mut node: Node = Node::init(board.play_onestep(Action::action_from_str("c1")), None);
+        assert!(node.board.status() == BoardStatus::Pass);
+        let mut rng: StdRng = StdRng::seed_from_u64(0);
+        let config: SearchConfig = SearchConfig {
+            expand_threshold:   1,
+            rollout_policy: &UniformRollout,
+            tree_policy:    TreePolicy::Ucb1(1.),
+            rave:   None,
+            evaluator:  &CellEval::default(),
+        };
+        for _ in 0..20 {
+            node.evaluate(&config, &mut rng, &mut vec![]);
+        }
+        assert_eq!(node.child_nodes.len(), 1);
+        assert!(node.child_nodes[0].action.is_none());
+        assert_eq!(node.sum_w, 0.);
+
+        let action: Action = seeded_mcts().next_action_option(&board).unwrap();
+        assert!(board.legal_actions().contains(&action));
+    }
+
+    #[test]
+    fn tree_is_reused_after_opponent_reply() {
+        let mcts: MCTS = seeded_mcts();
+        let board: Board = Board::init();
+        let action: Action = mcts.next_action_option(&board).unwrap();
+        let after_action: Board = board.play_onestep(action);
+        let reply: Action = after_action.legal_actions()[0];
+        let next_board: Board = after_action.play_onestep(reply);
+
+        let retained_count: u32 = mcts.tree.borrow().as_ref().unwrap()
+            .child_nodes.iter().find(|node| node.action == Some(action)).unwrap()
+            .child_nodes.iter().find(|node| node.action == Some(reply)).map_or(0, |node| node.try_count);
+        let root_node: Node = mcts.take_root_node(&next_board, &mcts.search_config());
+        assert_eq!(root_node.board.state, next_board.state);
+        assert_eq!(root_node.try_count, retained_count);
+    }
+    fn config_with(tree_policy: TreePolicy, evaluator: &dyn EvalTrait) -> SearchConfig<'_> {
+        SearchConfig {
+            expand_threshold:   1,
+            rollout_policy: &UniformRollout,
+            tree_policy,
+            rave:   None,
+            evaluator,
+        }
+    }
+
+    // sum_w, sum_w2は子の手番側から見た値
+    fn child_with(try_count: u32, sum_w: ValueType, sum_w2: ValueType, prior: ValueType) -> Node {
+        let mut node: Node = Node::init(Board::init(), None);
+        node.try_count = try_count;
+        node.sum_w = sum_w;
+        node.sum_w2 = sum_w2;
+        node.prior = prior;
+        node
+    }
+
+    fn root_with(child_nodes: Vec<Node>) -> Node {
+        let mut node: Node = Node::init(Board::init(), None);
+        node.try_count = child_nodes.iter().map(|node| node.try_count).sum();
+        node.child_nodes = child_nodes;
+        node
+    }
+
+    #[test]
+    fn zero_temperature_priors_go_to_best_children() {
+        let mut rng: StdRng = StdRng::seed_from_u64(0);
+        let evaluator: CellEval = CellEval::default();
+        for game in 0..10 {
+            let board: Board = random_board(4 + 3 * game, &mut rng);
+            if board.status() != BoardStatus::Usual {
+                continue;
+            }
+            let mut node: Node = Node::init(board, None);
+            node.expand(&MCTS::new(1, 0).search_config());
+            node.set_priors(&evaluator, 0.);
+            let scores: Vec<ScoreType> = node.child_nodes.iter().map(|node| -evaluator.eval(&node.board)).collect();
+            let max_score: ScoreType = *scores.iter().max().unwrap();
+            let best_num: usize = scores.iter().filter(|&&score| score == max_score).count();
+            for (child, score) in node.child_nodes.iter().zip(scores) {
+                let expected: ValueType = if score == max_score { 1. / best_num as ValueType } else { 0. };
+                assert!((child.prior - expected).abs() < 1e-12);
             }
         }
+    }
 
-        res_action
+    // 平均が同じなら分散が大きい子を選ぶ. 未訪問の子があればそれを先に選ぶ
+    #[test]
+    fn ucb1_tuned_prefers_high_variance() {
+        let evaluator: CellEval = CellEval::default();
+        let config: SearchConfig = config_with(TreePolicy::Ucb1Tuned, &evaluator);
+        let steady: Node = child_with(1000, 500., 250., 1.);    // 結果はすべて0.5
+        let noisy: Node = child_with(1000, 500., 500., 1.);     // 結果は0か1
+        assert_eq!(root_with(vec![steady, noisy]).next_child_node_index(&config), 1);
+
+        let steady: Node = child_with(1000, 500., 250., 1.);
+        let noisy: Node = child_with(1000, 500., 500., 1.);
+        assert_eq!(root_with(vec![steady, noisy, child_with(0, 0., 0., 1.)]).next_child_node_index(&config), 2);
+    }
+
+    // 訪問回数が同じなら事前確率の項で勝率の差を覆す. 未訪問の子を優先はしない
+    #[test]
+    fn puct_weights_priors() {
+        let child_nodes = || vec![
+            child_with(10, 4., 4., 0.1),    // 勝率0.6
+            child_with(10, 5., 5., 0.9),    // 勝率0.5
+            child_with(0, 0., 0., 0.),
+        ];
+        let evaluator: CellEval = CellEval::default();
+        let config: SearchConfig = config_with(TreePolicy::Puct { c: 1., temperature: 1. }, &evaluator);
+        assert_eq!(root_with(child_nodes()).next_child_node_index(&config), 1);
+        let config: SearchConfig = config_with(TreePolicy::Puct { c: 0., temperature: 1. }, &evaluator);
+        assert_eq!(root_with(child_nodes()).next_child_node_index(&config), 0);
+    }
+    fn action_node(bitboard: BitBoard) -> Node {
+        Node::init(Board::init(), Some(Action::action_from_bitboard(bitboard)))
     }
-}
\ No newline at end of file
+
+    // 手番側の手だけ数え, パスも1手として手番を交互に進める
+    #[test]
+    fn amaf_credits_own_moves() {
+        let mut node: Node = root_with(vec![action_node(A1), action_node(B1), action_node(A2), action_node(B2)]);
+        // 自分a1, 相手b1, 自分パス, 相手a2, 自分b2
+        node.update_amaf(&[A1, B1, 0, A2, B2], 1.);
+        let amaf_counts: Vec<u32> = node.child_nodes.iter().map(|node| node.amaf_count).collect();
+        assert_eq!(amaf_counts, vec![1, 0, 0, 1]);
+        // amaf_wは子の手番側から見た値
+        assert_eq!(node.child_nodes[0].amaf_w, 0.);
+
+        node.update_amaf(&[B1, A1], 0.);
+        let amaf_counts: Vec<u32> = node.child_nodes.iter().map(|node| node.amaf_count).collect();
+        assert_eq!(amaf_counts, vec![1, 1, 0, 1]);
+        assert_eq!(node.child_nodes[1].amaf_w, 1.);
+    }
+
+    #[test]
+    fn rave_blends_amaf_win_rate() {
+        let mut node: Node = child_with(10, 7., 4.9, 1.);  // 勝率0.3
+        assert!((node.win_rate(Some(RaveSchedule::Equivalence(30.))) - 0.3).abs() < 1e-12);
+
+        node.amaf_count = 30;
+        node.amaf_w = 6.;   // AMAFの勝率0.8
+        let beta: ValueType = 0.5_f64.sqrt();
+        assert!((node.win_rate(Some(RaveSchedule::Equivalence(30.))) - ((1. - beta) * 0.3 + beta * 0.8)).abs() < 1e-12);
+        // バイアス0ならbeta = m / (n + m)
+        assert!((node.win_rate(Some(RaveSchedule::MinimumMse(0.))) - (0.25 * 0.3 + 0.75 * 0.8)).abs() < 1e-12);
+        assert!((node.win_rate(None) - 0.3).abs() < 1e-12);
+    }
+
+    // ルートの子の手は必ずプレイアウトの最初の手なので, AMAFの回数は訪問回数以上になる
+    #[test]
+    fn rave_search_updates_root_amaf() {
+        let mut node: Node = Node::init(Board::init(), None);
+        let mut rng: StdRng = StdRng::seed_from_u64(0);
+        let evaluator: CellEval = CellEval::default();
+        let mut config: SearchConfig = config_with(TreePolicy::Ucb1(1.), &evaluator);
+        config.rave = Some(RaveSchedule::Equivalence(100.));
+        for _ in 0..200 {
+            node.evaluate(&config, &mut rng, &mut vec![]);
+        }
+        for child in node.child_nodes.iter() {
+            assert!(child.amaf_count >= child.try_count);
+            assert!(child.amaf_w <= child.amaf_count as ValueType);
+        }
+        assert!(node.child_nodes.iter().any(|child| child.amaf_count > child.try_count));
+    }
+    // 未訪問の子は訪問済みの子があれば選ばない
+    #[test]
+    fn final_selection_variants() {
+        let evaluator: CellEval = CellEval::default();
+        let node: Node = root_with(vec![
+            child_with(100, 50., 50., 1.),  // 勝率0.5
+            child_with(20, 6., 6., 1.),     // 勝率0.7
+            child_with(0, 0., 0., 1.),
+        ]);
+        assert_eq!(node.final_child_index(FinalSelection::MaxVisits, &evaluator), Some(0));
+        assert_eq!(node.final_child_index(FinalSelection::MaxValue, &evaluator), Some(1));
+        // 勝率最大の子は訪問回数が少ないので訪問回数で選ぶ
+        assert_eq!(node.final_child_index(FinalSelection::RobustMax, &evaluator), Some(0));
+        // 下限は0.5 - 1 / 10 = 0.4 と 0.7 - 1 / sqrt(20) = 0.476...
+        assert_eq!(node.final_child_index(FinalSelection::SecureChild(1.), &evaluator), Some(1));
+        // 下限は0.5 - 3 / 10 = 0.2 と 0.7 - 3 / sqrt(20) = 0.029...
+        assert_eq!(node.final_child_index(FinalSelection::SecureChild(3.), &evaluator), Some(0));
+    }
+
+    // 訪問回数が最大の子より勝率が高く, 訪問回数も同じ子があればそちらを選ぶ
+    #[test]
+    fn robust_max_prefers_value_on_equal_visits() {
+        let evaluator: CellEval = CellEval::default();
+        let node: Node = root_with(vec![
+            child_with(60, 24., 24., 1.),   // 勝率0.6
+            child_with(60, 30., 30., 1.),   // 勝率0.5
+        ]);
+        assert_eq!(node.final_child_index(FinalSelection::MaxVisits, &evaluator), Some(1));
+        assert_eq!(node.final_child_index(FinalSelection::RobustMax, &evaluator), Some(0));
+
+        // 勝率が同じなら訪問回数の多い子
+        let node: Node = root_with(vec![
+            child_with(10, 2., 2., 1.),     // 勝率0.8
+            child_with(50, 10., 10., 1.),   // 勝率0.8
+        ]);
+        assert_eq!(node.final_child_index(FinalSelection::RobustMax, &evaluator), Some(1));
+    }
+
+    #[test]
+    fn unvisited_root_falls_back_to_static_eval() {
+        let mut rng: StdRng = StdRng::seed_from_u64(0);
+        let evaluator: CellEval = CellEval::default();
+        for game in 0..10 {
+            let board: Board = random_board(4 + 3 * game, &mut rng);
+            if board.status() != BoardStatus::Usual {
+                continue;
+            }
+            let mut node: Node = Node::init(board, None);
+            node.expand(&MCTS::new(1, 0).search_config());
+            let best_score: ScoreType = node.child_nodes.iter().map(|node| -evaluator.eval(&node.board)).max().unwrap();
+            for selection in [FinalSelection::MaxVisits, FinalSelection::MaxValue, FinalSelection::RobustMax, FinalSelection::SecureChild(1.)] {
+                let index: usize = node.final_child_index(selection, &evaluator).unwrap();
+                assert_eq!(-evaluator.eval(&node.child_nodes[index].board), best_score);
+            }
+        }
+        assert!(root_with(vec![]).final_child_index(FinalSelection::MaxVisits, &evaluator).is_none());
+    }
+}
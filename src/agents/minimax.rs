@@ -1,21 +1,39 @@
 use std::time::Instant;
 
 use super::Agent;
-use crate::{components::{action::Action, constants::{ScoreType, INF, TIME_LIMT}, board::Board, enums::BoardStatus}, evals::{cell_score::CellEval, EvalTrait}};
+use crate::{components::{action::Action, constants::{ScoreType, INF, TIME_LIMT}, board::Board, bitboard::BitBoard, enums::BoardStatus}, evals::{cell_score::CellEval, EvalTrait}};
 
 pub struct MiniMaxAgent {
     pub depth: i32,
     pub rest_time:  u128,    // micro sec
+    pub evaluator:  Box<dyn EvalTrait>,
+}
+
+impl MiniMaxAgent {
+    pub fn new(depth: i32, rest_time: u128) -> MiniMaxAgent {
+        MiniMaxAgent::with_evaluator(depth, rest_time, Box::new(CellEval::default()))
+    }
+
+    pub fn with_evaluator(depth: i32, rest_time: u128, evaluator: Box<dyn EvalTrait>) -> MiniMaxAgent {
+        MiniMaxAgent {
+            depth,
+            rest_time,
+            evaluator,
+        }
+    }
 }
 
 impl Agent for MiniMaxAgent {
     fn next_action_option(&self, board: &Board) -> Option<Action> {
         let now = Instant::now();
+        let mut tmp_board: Board = *board;
         let mut best_action: Option<Action> = None;
         let mut best_score: ScoreType = -INF;
         for action in board.legal_actions() {
-            let next_board: Board = board.play_onestep(action);
-            let score: ScoreType = -self.minimax_score(&next_board, self.depth, now);
+            let flips: BitBoard = tmp_board.state.flips(action);
+            tmp_board.make_move(action, flips);
+            let score: ScoreType = -self.minimax_score(&mut tmp_board, self.depth, now);
+            tmp_board.undo_move(action, flips);
             if score > best_score {
                 best_score = score;
                 best_action = Some(action);
@@ -26,29 +44,33 @@ impl Agent for MiniMaxAgent {
 }
 
 impl MiniMaxAgent {
-    fn minimax_score(&self, board: &Board, depth: i32, now: Instant) -> ScoreType {
+    fn minimax_score(&self, board: &mut Board, depth: i32, now: Instant) -> ScoreType {
         if TIME_LIMT < now.elapsed().as_micros() + self.rest_time {
-            return CellEval::eval(*board);
+            return self.evaluator.eval(board);
         }
 
         if board.status() == BoardStatus::Finished || depth == 0 {
-            return CellEval::eval(*board);
+            return self.evaluator.eval(board);
         }
         let legal_actions: Vec<Action> = board.legal_actions();
         if legal_actions.is_empty() {
             match board.status() {
-                BoardStatus::Finished => { return CellEval::eval(*board); },
+                BoardStatus::Finished => { return self.evaluator.eval(board); },
                 BoardStatus::Pass => {
-                    let next_board: Board = (*board).play_pass();
-                    return -self.minimax_score(&next_board, depth, now);
+                    board.make_pass();
+                    let score: ScoreType = -self.minimax_score(board, depth, now);
+                    board.undo_pass();
+                    return score;
                 },
                 _ => {},
             }
         }
         let mut best_score: ScoreType = -INF;
         for action in legal_actions {
-            let next_board: Board = (*board).play_onestep(action);
-            let score: ScoreType = -self.minimax_score(&next_board, depth-1, now);
+            let flips: BitBoard = board.state.flips(action);
+            board.make_move(action, flips);
+            let score: ScoreType = -self.minimax_score(board, depth-1, now);
+            board.undo_move(action, flips);
             if score > best_score {
                 best_score = score;
             }
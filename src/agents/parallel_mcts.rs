@@ -0,0 +1,206 @@
+use std::{sync::{Mutex, atomic::{AtomicU32, Ordering}}, thread, time::Instant};
+
+use rand::{rngs::StdRng, SeedableRng};
+
+use super::{Agent, mcts::{FinalSelection, Node, SearchConfig, TreePolicy, playout_score}, rollout::{RolloutPolicy, UniformRollout}};
+use crate::{components::{action::Action, board::Board, bitboard::BitBoard, constants::{TIME_LIMT, BOARD_SIZE}}, evals::{cell_score::CellEval, EvalTrait}};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParallelMode {
+    Root,   // スレッドごとに独立した木を作り, ルートの訪問回数を合計する
+    Tree,   // 1つの木を共有し, 仮想敗北で各スレッドの探索を散らす
+}
+
+// オフライン解析・自己対戦用の並列MCTS
+// thread_num = 1 かつ seedとmax_playoutsを指定すれば結果は再現する
+#[allow(clippy::upper_case_acronyms)]
+pub struct ParallelMCTS {
+    pub expand_threshold:  u32,
+    pub rest_time: u128, // micro sec
+    pub thread_num: usize,
+    pub mode:   ParallelMode,
+    pub seed:   Option<u64>,
+    pub max_playouts:   Option<u32>,    // 全スレッド合計のプレイアウト回数. 指定すると時間制限の代わりに使う
+    pub rollout_policy: Box<dyn RolloutPolicy>,
+    pub tree_policy:    TreePolicy,
+    pub evaluator:  Box<dyn EvalTrait>,  // PUCTの事前確率に使う
+}
+
+impl ParallelMCTS {
+    pub fn new(expand_threshold: u32, rest_time: u128, thread_num: usize, mode: ParallelMode) -> ParallelMCTS {
+        assert!(thread_num > 0, "ParallelMCTS needs at least one thread");
+        ParallelMCTS {
+            expand_threshold,
+            rest_time,
+            thread_num,
+            mode,
+            seed:   None,
+            max_playouts:   None,
+            rollout_policy: Box::new(UniformRollout),
+            tree_policy:    TreePolicy::Ucb1(1.),
+            evaluator:  Box::new(CellEval::default()),
+        }
+    }
+
+    fn search_config(&self) -> SearchConfig<'_> {
+        SearchConfig {
+            expand_threshold:   self.expand_threshold,
+            rollout_policy: self.rollout_policy.as_ref(),
+            tree_policy:    self.tree_policy,
+            rave:   None,
+            evaluator:  self.evaluator.as_ref(),
+        }
+    }
+
+    fn thread_rng(&self, thread_index: usize) -> StdRng {
+        match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed.wrapping_add(thread_index as u64)),
+            None => StdRng::from_entropy(),
+        }
+    }
+
+    // 回数の上限があれば時刻は見ない. 負荷で結果が変わらないようにする
+    fn is_finished(&self, now: Instant, playout_count: u32, max_playouts: Option<u32>) -> bool {
+        match max_playouts {
+            Some(max_playouts) => max_playouts <= playout_count,
+            None => TIME_LIMT <= now.elapsed().as_micros() + self.rest_time,
+        }
+    }
+
+    // 各スレッドの木のルートの訪問回数を1つ目の木に合計して返す
+    fn root_parallel(&self, board: &Board, now: Instant) -> Node {
+        let thread_max_playouts: Option<u32> = self.max_playouts.map(|max_playouts| max_playouts.div_ceil(self.thread_num as u32));
+        let mut thread_results: Vec<Node> = thread::scope(|scope| {
+            let handles: Vec<_> = (0..self.thread_num).map(|thread_index| {
+                scope.spawn(move || {
+                    let mut rng: StdRng = self.thread_rng(thread_index);
+                    let config: SearchConfig = self.search_config();
+                    let mut root_node: Node = Node::init(*board, None);
+                    root_node.expand(&config);
+                    let mut moves: Vec<BitBoard> = Vec::with_capacity(BOARD_SIZE);
+                    let mut playout_count: u32 = 0;
+                    while !self.is_finished(now, playout_count, thread_max_playouts) {
+                        moves.clear();
+                        root_node.evaluate(&config, &mut rng, &mut moves);
+                        playout_count += 1;
+                    }
+                    root_node
+                })
+            }).collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+        });
+
+        // 子ノードの並びは全スレッドで同じ
+        let mut merged: Node = thread_results.swap_remove(0);
+        for result in thread_results.iter() {
+            for (merged_node, node) in merged.child_nodes.iter_mut().zip(result.child_nodes.iter()) {
+                merged_node.try_count += node.try_count;
+            }
+        }
+        merged
+    }
+
+    fn tree_parallel(&self, board: &Board, now: Instant) -> Node {
+        let config: SearchConfig = self.search_config();
+        let mut root_node: Node = Node::init(*board, None);
+        root_node.expand(&config);
+        let tree: Mutex<Node> = Mutex::new(root_node);
+        let playout_count: AtomicU32 = AtomicU32::new(0);
+
+        thread::scope(|scope| {
+            for thread_index in 0..self.thread_num {
+                let tree: &Mutex<Node> = &tree;
+                let playout_count: &AtomicU32 = &playout_count;
+                let config: &SearchConfig = &config;
+                scope.spawn(move || {
+                    let mut rng: StdRng = self.thread_rng(thread_index);
+                    let mut moves: Vec<BitBoard> = Vec::with_capacity(BOARD_SIZE);
+                    while !self.is_finished(now, playout_count.fetch_add(1, Ordering::Relaxed), self.max_playouts) {
+                        moves.clear();
+                        let (path, leaf_board) = tree.lock().unwrap().select_with_virtual_loss(config);
+                        // プレイアウトはロックの外で行う
+                        let value = playout_score(&leaf_board, self.rollout_policy.as_ref(), &mut rng, &mut moves);
+                        tree.lock().unwrap().backpropagate(&path, value, config);
+                    }
+                });
+            }
+        });
+
+        tree.into_inner().unwrap()
+    }
+}
+
+impl Agent for ParallelMCTS {
+    fn next_action_option(&self, board: &Board) -> Option<Action> {
+        let now = Instant::now();
+        let root_node: Node = match self.mode {
+            ParallelMode::Root => self.root_parallel(board, now),
+            ParallelMode::Tree => self.tree_parallel(board, now),
+        };
+        // 1回もプレイアウトできなければ着手後の静的評価で選ぶ
+        root_node.final_child_index(FinalSelection::MaxVisits, self.evaluator.as_ref())
+            .and_then(|index| root_node.child_nodes[index].action)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seeded_mcts(mode: ParallelMode) -> ParallelMCTS {
+        let mut mcts: ParallelMCTS = ParallelMCTS::new(2, 0, 1, mode);
+        mcts.seed = Some(0);
+        mcts.max_playouts = Some(200);
+        mcts
+    }
+
+    fn root_stats(mcts: &ParallelMCTS, board: &Board) -> Vec<(Option<Action>, u32)> {
+        let root_node: Node = match mcts.mode {
+            ParallelMode::Root => mcts.root_parallel(board, Instant::now()),
+            ParallelMode::Tree => mcts.tree_parallel(board, Instant::now()),
+        };
+        root_node.child_nodes.iter()
+            .map(|node| (node.action, node.try_count))
+            .collect()
+    }
+
+    #[test]
+    #[should_panic]
+    fn new_rejects_zero_threads() {
+        ParallelMCTS::new(2, 0, 0, ParallelMode::Root);
+    }
+
+    #[test]
+    fn single_thread_is_deterministic() {
+        let board: Board = Board::init();
+        for mode in [ParallelMode::Root, ParallelMode::Tree] {
+            let stats: Vec<(Option<Action>, u32)> = root_stats(&seeded_mcts(mode), &board);
+            assert_eq!(stats.iter().map(|&(_, count)| count).sum::<u32>(), 200);
+            assert_eq!(root_stats(&seeded_mcts(mode), &board), stats);
+            assert_eq!(seeded_mcts(mode).next_action(&board), seeded_mcts(mode).next_action(&board));
+        }
+    }
+    // スレッド数で割り切れなくても各スレッドは切り上げた回数だけ探索する
+    #[test]
+    fn playouts_are_rounded_up_per_thread() {
+        let board: Board = Board::init();
+        for (mode, expected) in [(ParallelMode::Root, 4), (ParallelMode::Tree, 3)] {
+            let mut mcts: ParallelMCTS = ParallelMCTS::new(2, TIME_LIMT, 4, mode);
+            mcts.max_playouts = Some(3);
+            assert_eq!(root_stats(&mcts, &board).iter().map(|&(_, count)| count).sum::<u32>(), expected);
+            let action: Action = mcts.next_action_option(&board).unwrap();
+            assert!(board.legal_actions().contains(&action));
+        }
+    }
+
+    #[test]
+    fn no_playout_falls_back_to_static_eval() {
+        let board: Board = Board::init();
+        for mode in [ParallelMode::Root, ParallelMode::Tree] {
+            let mut mcts: ParallelMCTS = ParallelMCTS::new(2, 0, 2, mode);
+            mcts.max_playouts = Some(0);
+            let action: Action = mcts.next_action_option(&board).unwrap();
+            assert!(board.legal_actions().contains(&action));
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use super::search::{SearchAgent, SearchAlgorithm, SearchContext};
+use crate::components::{action::Action, constants::ScoreType, board::Board, zobrist::HashType};
+
+// Principal Variation Search (NegaScout)
+// 先頭の手以外はnull windowで調べ, alphaを超えたときだけ通常の窓で再探索する
+pub struct Pvs;
+
+pub type PvsAgent = SearchAgent<Pvs>;
+
+impl SearchAlgorithm for Pvs {
+    fn child_score(board: &mut Board, hash: HashType, action: Action, depth: i32, alpha: ScoreType, beta: ScoreType, first: bool, ply: usize, on_pv: bool, context: &mut SearchContext) -> Option<ScoreType> {
+        if first {
+            return context.score_after_move::<Pvs>(board, hash, action, depth, alpha, beta, ply, on_pv);
+        }
+        let score: ScoreType = context.score_after_move::<Pvs>(board, hash, action, depth, alpha, alpha + 1, ply, on_pv)?;
+        if alpha < score && score < beta {
+            context.score_after_move::<Pvs>(board, hash, action, depth, score, beta, ply, on_pv)
+        } else {
+            Some(score)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::{agents::{Agent, alphabeta::AlphaBetaAgent, search::SearchInfo}, components::enums::BoardStatus, test_utils::random_board};
+
+    fn fixed_depth<S: SearchAlgorithm>(depth: i32) -> SearchAgent<S> {
+        let mut agent: SearchAgent<S> = SearchAgent::new(depth, 0);
+        agent.time_limit = None;
+        agent
+    }
+
+    // 同じ深さならalpha-betaと同じ評価値で, 探索ノード数は合計で多くならない
+    #[test]
+    fn matches_alpha_beta() {
+        const DEPTH: i32 = 4;
+        let mut rng: StdRng = StdRng::seed_from_u64(0);
+        let (mut alpha_beta_nodes, mut pvs_nodes) = (0, 0);
+        for game in 0..10 {
+            let board: Board = random_board(6 + 2 * game, &mut rng);
+            if board.status() != BoardStatus::Usual {
+                continue;
+            }
+
+            let alpha_beta: AlphaBetaAgent = fixed_depth(DEPTH);
+            let pvs: PvsAgent = fixed_depth(DEPTH);
+            alpha_beta.next_action(&board);
+            pvs.next_action(&board);
+            let (expected, actual): (SearchInfo, SearchInfo) = (alpha_beta.search_info(), pvs.search_info());
+            assert_eq!((expected.depth, actual.depth), (DEPTH, DEPTH));
+            assert_eq!(actual.score, expected.score);
+            // 同点の手が複数あると選ぶ手は変わりうるので, 選んだ手の評価値を比べる
+            let after: Board = board.play_onestep(actual.pv[0]);
+            let check: AlphaBetaAgent = fixed_depth(DEPTH - 1);
+            match after.status() {
+                BoardStatus::Usual => {
+                    check.next_action(&after);
+                    assert_eq!(-check.search_info().score, expected.score);
+                },
+                BoardStatus::Pass => {
+                    check.next_action(&after.play_pass());
+                    assert_eq!(check.search_info().score, expected.score);
+                },
+                _ => {},
+            }
+            alpha_beta_nodes += expected.nodes;
+            pvs_nodes += actual.nodes;
+        }
+        assert!(pvs_nodes <= alpha_beta_nodes, "pvs {} alpha-beta {}", pvs_nodes, alpha_beta_nodes);
+    }
+}
@@ -7,6 +7,6 @@ pub struct RandomAgent {}
 
 impl Agent for RandomAgent {
     fn next_action_option(&self, board: &Board) -> Option<Action> {
-        board.legal_actions().choose(&mut rand::thread_rng()).map(|x| x.clone())
+        board.legal_actions().choose(&mut rand::thread_rng()).copied()
     }
 }
@@ -0,0 +1,95 @@
+use rand::{rngs::StdRng, seq::SliceRandom, distributions::WeightedIndex, prelude::Distribution};
+
+use crate::{components::{action::Action, board::Board, bitboard::BitBoard, constants::ScoreType}, evals::{cell_score::CellEval, EvalTrait}};
+
+const CORNER_BIT: BitBoard = 0x8100000000000081;
+const X_SQUARE_BIT: BitBoard = 0x0042000000004200;
+
+// MCTSのプレイアウトで手を選ぶ方策. actionsは空でない
+pub trait RolloutPolicy: Send + Sync {
+    fn choose(&self, board: &Board, actions: &[Action], rng: &mut StdRng) -> Action;
+}
+
+pub struct UniformRollout;
+
+impl RolloutPolicy for UniformRollout {
+    fn choose(&self, _: &Board, actions: &[Action], rng: &mut StdRng) -> Action {
+        *actions.choose(rng).unwrap()
+    }
+}
+
+// 角が取れるなら取り, それ以外はXを避けてランダムに選ぶ
+pub struct CornerGreedyRollout;
+
+impl RolloutPolicy for CornerGreedyRollout {
+    fn choose(&self, _: &Board, actions: &[Action], rng: &mut StdRng) -> Action {
+        let corner_actions: Vec<Action> = actions.iter().copied().filter(|action| action.bitboard & CORNER_BIT != 0).collect();
+        if let Some(action) = corner_actions.choose(rng) {
+            return *action;
+        }
+        let safe_actions: Vec<Action> = actions.iter().copied().filter(|action| action.bitboard & X_SQUARE_BIT == 0).collect();
+        *safe_actions.choose(rng).or_else(|| actions.choose(rng)).unwrap()
+    }
+}
+
+// 着手後の評価値のsoftmaxに比例する確率で選ぶ. temperatureが小さいほど貪欲になり, 0以下なら最大の手から選ぶ
+pub struct EvalWeightedRollout {
+    pub temperature:    f64,
+    pub evaluator:  Box<dyn EvalTrait>,
+}
+
+impl EvalWeightedRollout {
+    pub fn new(temperature: f64) -> EvalWeightedRollout {
+        EvalWeightedRollout {
+            temperature,
+            evaluator:  Box::new(CellEval::default()),
+        }
+    }
+}
+
+impl RolloutPolicy for EvalWeightedRollout {
+    fn choose(&self, board: &Board, actions: &[Action], rng: &mut StdRng) -> Action {
+        let scores: Vec<ScoreType> = actions.iter()
+            .map(|&action| -self.evaluator.eval(&board.play_onestep(action)))
+            .collect();
+        let max_score: ScoreType = *scores.iter().max().unwrap();
+        if self.temperature <= 0. {
+            let best_actions: Vec<Action> = actions.iter().zip(scores.iter())
+                .filter(|&(_, &score)| score == max_score)
+                .map(|(&action, _)| action)
+                .collect();
+            return *best_actions.choose(rng).unwrap();
+        }
+        let weights: Vec<f64> = scores.iter()
+            .map(|&score| ((score - max_score) as f64 / self.temperature).exp())
+            .collect();
+        let distribution: WeightedIndex<f64> = WeightedIndex::new(&weights).unwrap();
+        actions[distribution.sample(rng)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::{components::enums::BoardStatus, test_utils::random_board};
+
+    #[test]
+    fn zero_temperature_chooses_best_action() {
+        let mut rng: StdRng = StdRng::seed_from_u64(0);
+        let policy: EvalWeightedRollout = EvalWeightedRollout::new(0.);
+        for game in 0..10 {
+            let board: Board = random_board(4 + 3 * game, &mut rng);
+            if board.status() != BoardStatus::Usual {
+                continue;
+            }
+            let actions: Vec<Action> = board.legal_actions();
+            let score = |action: Action| -policy.evaluator.eval(&board.play_onestep(action));
+            let best_score: ScoreType = actions.iter().map(|&action| score(action)).max().unwrap();
+            for _ in 0..5 {
+                assert_eq!(score(policy.choose(&board, &actions, &mut rng)), best_score);
+            }
+        }
+    }
+}
@@ -0,0 +1,239 @@
+use std::{cell::RefCell, marker::PhantomData, time::Instant};
+
+use super::Agent;
+use crate::{components::{action::Action, constants::{ScoreType, INF, TIME_LIMT}, board::Board, bitboard::{BitBoard, BitBoardTrait}, enums::{BoardStatus, BoundType}, move_ordering::{MoveOrderer, OrderingConfig, order_action_first}, state::State, transposition_table::{TranspositionTable, TableStats, DEFAULT_TABLE_SIZE}, zobrist::HashType}, evals::{cell_score::CellEval, EvalTrait}};
+
+// 最後に完了した反復の結果
+#[derive(Debug, Clone, Default)]
+pub struct SearchInfo {
+    pub depth:  i32,
+    pub score:  ScoreType,
+    pub pv: Vec<Action>,
+    pub nodes:  u64,    // 全反復の合計探索ノード数
+}
+
+// 1回のnext_action_optionの間だけ使う探索の状態
+pub struct SearchContext<'a> {
+    pub(super) now:    Instant,
+    pub(super) time_limit: Option<u128>,   // micro sec. Noneなら時刻を見ない
+    pub(super) rest_time:  u128,
+    pub(super) evaluator:  &'a dyn EvalTrait,
+    pub(super) table:  &'a mut TranspositionTable,
+    pub(super) orderer:    MoveOrderer,
+    pub(super) pv: Vec<Action>,    // 前の反復の読み筋
+    pub(super) nodes:  u64,
+}
+
+impl SearchContext<'_> {
+    pub(super) fn is_time_over(&self) -> bool {
+        self.time_limit.is_some_and(|time_limit| time_limit < self.now.elapsed().as_micros() + self.rest_time)
+    }
+
+    // 置換表の値で打ち切れるならErr(評価値), そうでなければOk(置換表の最善手)
+    fn probe(&mut self, hash: HashType, depth: i32, alpha: ScoreType, beta: ScoreType) -> Result<Option<Action>, ScoreType> {
+        let Some(entry) = self.table.probe(hash) else { return Ok(None); };
+        if entry.depth >= depth {
+            match entry.bound {
+                BoundType::Exact => { return Err(entry.score); },
+                BoundType::Lower if entry.score >= beta => { return Err(entry.score); },
+                BoundType::Upper if entry.score <= alpha => { return Err(entry.score); },
+                _ => {},
+            }
+        }
+        Ok(entry.best_action)
+    }
+
+    // 探索窓(alpha_orig, beta)に対するbest_scoreの種類を決めて置換表に書き込む
+    fn store(&mut self, hash: HashType, depth: i32, best_score: ScoreType, alpha_orig: ScoreType, beta: ScoreType, best_action: Option<Action>) {
+        let bound: BoundType = if best_score <= alpha_orig {
+            BoundType::Upper
+        } else if best_score >= beta {
+            BoundType::Lower
+        } else {
+            BoundType::Exact
+        };
+        self.table.store(hash, depth, best_score, bound, best_action);
+    }
+
+    // 置換表の手, 前の反復の読み筋の手の順に優先して並べた合法手と, 読み筋の手を返す
+    fn ordered_actions(&mut self, board: &Board, hash_action: Option<Action>, ply: usize, on_pv: bool) -> (Vec<Action>, Option<Action>) {
+        let mut legal_actions: Vec<Action> = board.legal_actions();
+        let pv_action: Option<Action> = if on_pv { self.pv.get(ply).copied() } else { None };
+        self.orderer.order(board, &mut legal_actions, hash_action, ply);
+        order_action_first(&mut legal_actions, pv_action);
+        (legal_actions, pv_action)
+    }
+
+    // ルートの探索. 子はdepthの深さで読み, 結果は深さdepth + 1として置換表に書き込む
+    pub(super) fn search_root<S: SearchAlgorithm>(&mut self, board: &Board, depth: i32) -> Option<(ScoreType, Action)> {
+        let mut tmp_board: Board = *board;
+        let hash: HashType = board.hash();
+        let mut best_action: Option<Action> = None;
+        let mut alpha: ScoreType = -INF;
+
+        let hash_action: Option<Action> = self.table.probe(hash).and_then(|entry| entry.best_action);
+        let (legal_actions, pv_action) = self.ordered_actions(board, hash_action, 0, true);
+        for action in legal_actions {
+            let score: ScoreType = S::child_score(&mut tmp_board, hash, action, depth, alpha, INF, best_action.is_none(), 0, pv_action == Some(action), self)?;
+            if best_action.is_none() || score > alpha {
+                alpha = score;
+                best_action = Some(action);
+            }
+        }
+        self.table.store(hash, depth + 1, alpha, BoundType::Exact, best_action);
+        best_action.map(|action| (alpha, action))
+    }
+
+    // 手番側から見た評価値. 時間切れの場合はNoneを返し, 置換表にも書き込まない
+    #[allow(clippy::too_many_arguments)]
+    pub(super) fn search<S: SearchAlgorithm>(&mut self, board: &mut Board, hash: HashType, depth: i32, mut alpha: ScoreType, beta: ScoreType, ply: usize, on_pv: bool) -> Option<ScoreType> {
+        if self.is_time_over() {
+            return None;
+        }
+        self.nodes += 1;
+
+        if board.status() == BoardStatus::Finished || depth == 0 {
+            return Some(self.evaluator.eval(board));
+        }
+
+        let alpha_orig: ScoreType = alpha;
+        let hash_action: Option<Action> = match self.probe(hash, depth, alpha, beta) {
+            Ok(hash_action) => hash_action,
+            Err(score) => { return Some(score); },
+        };
+
+        if board.status() == BoardStatus::Pass {
+            board.make_pass();
+            let result: Option<ScoreType> = self.search::<S>(board, State::hash_after_pass(hash), depth, -beta, -alpha, ply, on_pv);
+            board.undo_pass();
+            return result.map(|score| -score);
+        }
+        let (legal_actions, pv_action) = self.ordered_actions(board, hash_action, ply, on_pv);
+
+        let mut best_score: ScoreType = -INF;
+        let mut best_action: Option<Action> = None;
+        for action in legal_actions {
+            let score: ScoreType = S::child_score(board, hash, action, depth - 1, alpha, beta, best_action.is_none(), ply, pv_action == Some(action), self)?;
+            if score > best_score {
+                best_score = score;
+                best_action = Some(action);
+            }
+            if score > alpha {
+                alpha = score;
+            }
+            if alpha >= beta {
+                self.orderer.update_cutoff(board, action, depth, ply);
+                break;
+            }
+        }
+
+        self.store(hash, depth, best_score, alpha_orig, beta, best_action);
+        Some(best_score)
+    }
+
+    // actionを打った局面を(alpha, beta)で探索し, 手番側から見た評価値を返す
+    #[allow(clippy::too_many_arguments)]
+    pub(super) fn score_after_move<S: SearchAlgorithm>(&mut self, board: &mut Board, hash: HashType, action: Action, depth: i32, alpha: ScoreType, beta: ScoreType, ply: usize, on_pv: bool) -> Option<ScoreType> {
+        let flips: BitBoard = board.state.flips(action);
+        let next_hash: HashType = State::hash_after_move(hash, board.turn(), action, flips);
+        board.make_move(action, flips);
+        let result: Option<ScoreType> = self.search::<S>(board, next_hash, depth, -beta, -alpha, ply + 1, on_pv);
+        board.undo_move(action, flips);
+        result.map(|score| -score)
+    }
+}
+
+// 反復深化の各深さで使う探索法. 探索法ごとに違うのは子の探索窓の使い方だけ
+pub trait SearchAlgorithm {
+    // actionを打った局面を深さdepthで読み, 手番側から見た評価値を返す. firstはその局面で最初に読む手か
+    // 時間切れの場合はNoneを返す
+    #[allow(clippy::too_many_arguments)]
+    fn child_score(board: &mut Board, hash: HashType, action: Action, depth: i32, alpha: ScoreType, beta: ScoreType, first: bool, ply: usize, on_pv: bool, context: &mut SearchContext) -> Option<ScoreType>;
+}
+
+// 置換表を使った反復深化. 各反復の探索法はSで決まる
+pub struct SearchAgent<S: SearchAlgorithm> {
+    pub depth: i32,         // 反復深化の最大深さ
+    pub time_limit: Option<u128>,   // micro sec. Noneならdepthまで読み切る
+    pub rest_time:  u128,    // micro sec
+    pub ordering:   OrderingConfig,
+    pub evaluator:  Box<dyn EvalTrait>,
+    transposition_table:    RefCell<TranspositionTable>,
+    search_info:    RefCell<SearchInfo>,
+    algorithm:  PhantomData<S>,
+}
+
+impl<S: SearchAlgorithm> Agent for SearchAgent<S> {
+    fn next_action_option(&self, board: &Board) -> Option<Action> {
+        let now = Instant::now();
+        let legal_actions: Vec<Action> = board.legal_actions();
+        if legal_actions.is_empty() {
+            return None;
+        }
+
+        let table = &mut self.transposition_table.borrow_mut();
+        let mut context: SearchContext = SearchContext {
+            now,
+            time_limit: self.time_limit,
+            rest_time:  self.rest_time,
+            evaluator:  self.evaluator.as_ref(),
+            table,
+            orderer:    MoveOrderer::new(self.ordering),
+            pv: vec![],
+            nodes:  0,
+        };
+        let empty_num: i32 = (!(board.state.player_bit | board.state.opponent_bit)).count();
+        let mut info: SearchInfo = SearchInfo::default();
+        for depth in 0..=self.depth {
+            // 時間切れで中断した反復の結果は捨てる
+            let Some((score, action)) = context.search_root::<S>(board, depth) else { break; };
+            let pv: Vec<Action> = context.table.principal_variation(board, action, depth + 1);
+            context.pv = pv.clone();
+            info = SearchInfo {
+                depth,
+                score,
+                pv,
+                nodes:  context.nodes,
+            };
+            if depth + 1 >= empty_num { // 終局まで読み切った
+                break;
+            }
+        }
+
+        info.nodes = context.nodes;
+        let best_action: Action = info.pv.first().copied().unwrap_or(legal_actions[0]);
+        *self.search_info.borrow_mut() = info;
+        Some(best_action)
+    }
+}
+
+impl<S: SearchAlgorithm> SearchAgent<S> {
+    pub fn new(depth: i32, rest_time: u128) -> SearchAgent<S> {
+        SearchAgent::with_table_size(depth, rest_time, DEFAULT_TABLE_SIZE)
+    }
+
+    pub fn with_table_size(depth: i32, rest_time: u128, table_size: usize) -> SearchAgent<S> {
+        SearchAgent {
+            depth,
+            time_limit: Some(TIME_LIMT),
+            rest_time,
+            ordering:   OrderingConfig::default(),
+            evaluator:  Box::new(CellEval::default()),
+            transposition_table:    RefCell::new(TranspositionTable::new(table_size)),
+            search_info:    RefCell::new(SearchInfo::default()),
+            algorithm:  PhantomData,
+        }
+    }
+
+    pub fn table_stats(&self) -> TableStats {
+        self.transposition_table.borrow().stats()
+    }
+
+    pub fn table_memory_bytes(&self) -> usize {
+        self.transposition_table.borrow().memory_bytes()
+    }
+
+    pub fn search_info(&self) -> SearchInfo {
+        self.search_info.borrow().clone()
+    }
+}
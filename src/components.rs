@@ -3,4 +3,7 @@ pub mod enums;
 pub mod bitboard;
 pub mod board;
 pub mod state;
-pub mod action;
\ No newline at end of file
+pub mod action;
+pub mod zobrist;
+pub mod transposition_table;
+pub mod move_ordering;
\ No newline at end of file
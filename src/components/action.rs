@@ -1,6 +1,8 @@
+use std::fmt;
+
 use super::{bitboard::BitBoard, constants::{TOP_BIT, BOARD_SIZE}};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Action {
     pub bitboard: BitBoard,
 }
@@ -18,11 +20,11 @@ impl Action {
 
         let mut mask: u64 = TOP_BIT;
 
-        if num_x < 0 || 8 <= num_x {
+        if !(0..8).contains(&num_x) {
             panic!("Invalid action format: x is {}", num_x)
         }
 
-        if num_y < 0 || 8 <= num_y {
+        if !(0..8).contains(&num_y) {
             panic!("Invalid action format: y is {}", num_y)
         }
 
@@ -33,17 +35,19 @@ impl Action {
             bitboard: mask,
         }
     }
+}
 
-    pub fn to_string(&self) -> String {
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut mask:BitBoard = TOP_BIT;
         let mut res: String = String::new();
         for i in 0..BOARD_SIZE {
             if self.bitboard & mask != 0 {
-                res = format!("{}{}", (((i % 8) as u8 + 'a' as u8) as char), (((i / 8) as u8 + '1' as u8) as char));
+                res = format!("{}{}", (((i % 8) as u8 + b'a') as char), (((i / 8) as u8 + b'1') as char));
                 break;
             }
             mask >>= 1;
         }
-        res
+        write!(f, "{}", res)
     }
 }
\ No newline at end of file
@@ -1,23 +1,55 @@
-use super::constants::{TOP_BIT, BOARD_SIZE};
-
 pub type BitBoard = u64;
 
 pub trait BitBoardTrait {
     fn count(&self) -> i32;
+    fn flip_vertical(&self) -> BitBoard;
+    fn mirror_horizontal(&self) -> BitBoard;
+    fn flip_diagonal(&self) -> BitBoard;
+    fn symmetry(&self, index: usize) -> BitBoard;
 }
 
+pub const SYMMETRY_NUM: usize = 8;
+
 impl BitBoardTrait for BitBoard {
     fn count(&self) -> i32 {
-        let mut mask: BitBoard = TOP_BIT;
-        let mut count = 0;
+        self.count_ones() as i32
+    }
 
-        for _ in 0..BOARD_SIZE {
-            if mask & self != 0 {
-                count += 1;
-            }
-            mask >>= 1;
-        }
+    fn flip_vertical(&self) -> BitBoard { // 1行目と8行目を入れ替える
+        self.swap_bytes()
+    }
+
+    fn mirror_horizontal(&self) -> BitBoard { // A列とH列を入れ替える
+        let mut bit: BitBoard = *self;
+        bit = ((bit >> 1) & 0x5555555555555555) | ((bit & 0x5555555555555555) << 1);
+        bit = ((bit >> 2) & 0x3333333333333333) | ((bit & 0x3333333333333333) << 2);
+        bit = ((bit >> 4) & 0x0f0f0f0f0f0f0f0f) | ((bit & 0x0f0f0f0f0f0f0f0f) << 4);
+        bit
+    }
 
-        count
+    fn flip_diagonal(&self) -> BitBoard { // A1-H8の対角線で転置する
+        let mut bit: BitBoard = *self;
+        let mut tmp: BitBoard;
+        tmp = 0x0f0f0f0f00000000 & (bit ^ (bit << 28));
+        bit ^= tmp ^ (tmp >> 28);
+        tmp = 0x3333000033330000 & (bit ^ (bit << 14));
+        bit ^= tmp ^ (tmp >> 14);
+        tmp = 0x5500550055005500 & (bit ^ (bit << 7));
+        bit ^= tmp ^ (tmp >> 7);
+        bit
     }
-}
\ No newline at end of file
+
+    fn symmetry(&self, index: usize) -> BitBoard { // 盤面の8通りの対称変換. 0は恒等変換
+        let mut bit: BitBoard = *self;
+        if index & 1 != 0 {
+            bit = bit.flip_vertical();
+        }
+        if index & 2 != 0 {
+            bit = bit.mirror_horizontal();
+        }
+        if index & 4 != 0 {
+            bit = bit.flip_diagonal();
+        }
+        bit
+    }
+}
@@ -1,27 +1,21 @@
-use crate::agents::Agent;
-
-use super::{state::State, action::Action, enums::{Turn, TurnTrait, FIRST_TURN, BoardStatus, WinningStatus}, bitboard::{BitBoard, BitBoardTrait}, constants::{TOP_BIT, MAX_ACTION_NUM, BOARD_SIZE}};
+use super::{state::State, action::Action, enums::{Turn, TurnTrait, FIRST_TURN, BoardStatus, WinningStatus}, bitboard::{BitBoard, BitBoardTrait}, constants::{TOP_BIT, MAX_ACTION_NUM, BOARD_SIZE}, zobrist::HashType};
 
 
 // https://qiita.com/sensuikan1973/items/459b3e11d91f3cb37e43
 
 #[derive(Copy, Clone)]
-pub struct Board<'a> {
+pub struct Board {
     turn:   Turn,
     index:  i32,
     pub state:  State,
-    player_agent:   &'a dyn Agent,
-    opponent_agent: &'a dyn Agent,
 }
 
-impl<'a> Board<'a> {
-    pub fn init(player_agent: &'a dyn Agent, opponent_agent: &'a dyn Agent) -> Board<'a> {
+impl Board {
+    pub fn init() -> Board {
         Board {
             turn:   FIRST_TURN,
             index:  1,
             state:  State::init(),
-            player_agent,
-            opponent_agent,
         }
     }
 
@@ -29,31 +23,27 @@ impl<'a> Board<'a> {
         self.state = state;
     }
 
-    fn transfer(bit: BitBoard, k: i32) -> BitBoard {
-        match k {
-            0 => (bit << 8) & 0xffffffffffffff00, // 上
-            1 => (bit << 7) & 0x7f7f7f7f7f7f7f00, // 右上
-            2 => (bit >> 1) & 0x7f7f7f7f7f7f7f7f, // 右
-            3 => (bit >> 9) & 0x007f7f7f7f7f7f7f, // 右下
-            4 => (bit >> 8) & 0x00ffffffffffffff, // 下
-            5 => (bit >> 7) & 0x00fefefefefefefe, // 左下
-            6 => (bit << 1) & 0xfefefefefefefefe, // 左
-            7 => (bit << 9) & 0xfefefefefefefe00, // 左上
-            _ => panic!("undefined k was given in Board.transfer: k is {}", k)
-        }
+    pub fn turn(&self) -> Turn {
+        self.turn
+    }
+
+    pub fn index(&self) -> i32 {
+        self.index
+    }
+
+    pub fn hash(&self) -> HashType {
+        self.state.hash(self.turn)
+    }
+
+    pub fn canonical_hash(&self) -> HashType {
+        self.state.canonical_hash(self.turn)
     }
 
     pub fn status(&self) -> BoardStatus {
-        let tmp_board: Board = Board {
-            state: State {
-                player_bit: self.state.opponent_bit,
-                opponent_bit: self.state.player_bit,
-            },
-            ..*self
-        };
+        let tmp_state: State = State::build(self.state.opponent_bit, self.state.player_bit);
 
         let player_legal_actions_board: BitBoard = self.legal_actions_bitboard();
-        let opponent_legal_actions_board: BitBoard = tmp_board.legal_actions_bitboard();
+        let opponent_legal_actions_board: BitBoard = tmp_state.legal_actions_bitboard();
 
         if player_legal_actions_board == 0 && opponent_legal_actions_board != 0 {  // 先手番のみ置く場所がない
             BoardStatus::Pass
@@ -64,7 +54,7 @@ impl<'a> Board<'a> {
         }
     }
 
-    fn result(&self) -> (i32, i32, Turn) {
+    pub fn result(&self) -> (i32, i32, Turn) {
         let black_score: i32;
         let white_score: i32;
 
@@ -87,65 +77,41 @@ impl<'a> Board<'a> {
         (black_score, white_score, winner)
     }
 
-    pub fn play_onestep(self, action: Action) -> Board<'a> {
-        let mut reverse_board: BitBoard = 0;
-        for k in 0..8 {
-            let mut tmp_reverse_board: BitBoard = 0;
-            let mut mask: BitBoard = Board::transfer(action.bitboard, k);
-            while mask != 0 && (mask & self.state.opponent_bit) != 0 {
-                tmp_reverse_board |= mask;
-                mask = Board::transfer(mask, k);
-            }
-            if mask & self.state.player_bit != 0 {
-                reverse_board |= tmp_reverse_board;
-            }
-        }
+    pub fn play_onestep(self, action: Action) -> Board {
+        let mut res_board: Board = self;
+        res_board.make_move(action, self.state.flips(action));
+        res_board
+    }
 
-        let res_board = Board {
-            state:  State {
-                player_bit:     self.state.opponent_bit ^ reverse_board,
-                opponent_bit:   self.state.player_bit ^ (action.bitboard | reverse_board),
-            },
-            index:  self.index + 1,
-            turn:   self.turn.reverse(),
-            player_agent:   self.opponent_agent,
-            opponent_agent: self.player_agent,
-        };
+    pub fn play_pass(self) -> Board {
+        let mut res_board: Board = self;
+        res_board.make_pass();
         res_board
     }
 
-    pub fn play_pass(self) -> Board<'a> {
-        Board {
-            state:  State {
-                player_bit:     self.state.opponent_bit,
-                opponent_bit:   self.state.player_bit,
-            },
-            index:  self.index + 1,
-            turn:   self.turn.reverse(),
-            player_agent:   self.opponent_agent,
-            opponent_agent: self.player_agent,
-        }
+    // flipsには同じ局面でのself.state.flips(action)の結果を渡す
+    pub fn make_move(&mut self, action: Action, flips: BitBoard) {
+        self.state.make_move(action, flips);
+        self.index += 1;
+        self.turn = self.turn.reverse();
     }
 
-    pub fn playout(self, print_flag: bool) -> (i32, i32, Turn) {
-        let mut tmp: Board = self;
-        let mut tmp_status: BoardStatus = self.status();
-        while tmp_status != BoardStatus::Finished {
-            if tmp_status == BoardStatus::Pass {
-                tmp = tmp.play_pass();
-            } else {
-                let action: Action = tmp.player_agent.next_action(&tmp);
-                tmp = tmp.play_onestep(action);
-            }
-            tmp_status = tmp.status();
-        }
-        let result = tmp.result();
+    pub fn undo_move(&mut self, action: Action, flips: BitBoard) {
+        self.state.undo_move(action, flips);
+        self.index -= 1;
+        self.turn = self.turn.reverse();
+    }
 
-        if print_flag {
-            tmp.print();
-            println!("Result:{}", result.2);
-        }
-        result
+    pub fn make_pass(&mut self) {
+        self.state.make_pass();
+        self.index += 1;
+        self.turn = self.turn.reverse();
+    }
+
+    pub fn undo_pass(&mut self) {
+        self.state.make_pass();
+        self.index -= 1;
+        self.turn = self.turn.reverse();
     }
 
     pub fn winning_status(&self) -> WinningStatus {
@@ -185,7 +151,7 @@ impl<'a> Board<'a> {
             mask >>= 1;
 
             if i % 8 == 7 {
-                print!("{}\n", i / 8 + 1);
+                println!("{}", i / 8 + 1);
             }
         }
 
@@ -199,13 +165,7 @@ impl<'a> Board<'a> {
     }
 }
 
-// legal_actions_bitboardのみで使う.高速化のために外に出しておく(効果未検証)
-struct Direction {
-    watch_board: BitBoard,
-    shift_step: i32,
-}
-
-impl<'a> Board<'a> {
+impl Board {
     pub fn legal_actions(&self) -> Vec<Action> {
         let legal_bitboard: BitBoard = self.legal_actions_bitboard();
         let mut mask:BitBoard = TOP_BIT;
@@ -219,50 +179,15 @@ impl<'a> Board<'a> {
         actions
     }
 
-    fn legal_actions_bitboard(&self) -> BitBoard { // 着手可能なマスにフラグが立っている
-        let horizontal_watch_board: BitBoard = self.state.opponent_bit & 0x7e7e7e7e7e7e7e7e;
-        let vertical_watch_board: BitBoard = self.state.opponent_bit & 0x00FFFFFFFFFFFF00;
-        let all_side_watch_board: BitBoard = self.state.opponent_bit & 0x007e7e7e7e7e7e00;
-
-        let blank_board: BitBoard = !(self.state.player_bit | self.state.opponent_bit);
-
-        let mut legal_board: BitBoard = 0;
-        let directions: [Direction; 4];
-
-        directions = [
-            Direction {
-                watch_board: horizontal_watch_board,
-                shift_step: 1,
-            },
-            Direction {
-                watch_board: vertical_watch_board,
-                shift_step: 8,
-            },
-            Direction {
-                watch_board: all_side_watch_board,
-                shift_step: 7,
-            },
-            Direction {
-                watch_board: all_side_watch_board,
-                shift_step: 9,
-            },
-        ];
-
-        for dir in directions.iter() {
-            let mut tmp_board: BitBoard;
-
-            tmp_board = dir.watch_board & (self.state.player_bit << dir.shift_step);
-            for _ in 0..5 {
-                tmp_board |= dir.watch_board & (tmp_board << dir.shift_step);
-            }
-            legal_board |= blank_board & (tmp_board << dir.shift_step);
+    pub fn legal_actions_bitboard(&self) -> BitBoard { // 着手可能なマスにフラグが立っている
+        self.state.legal_actions_bitboard()
+    }
 
-            tmp_board = dir.watch_board & (self.state.player_bit >> dir.shift_step);
-            for _ in 0..5 {
-                tmp_board |= dir.watch_board & (tmp_board >> dir.shift_step);
-            }
-            legal_board |= blank_board & (tmp_board >> dir.shift_step);
-        }
-        legal_board
+    pub fn stable_bitboard(&self) -> BitBoard { // 確定石(両者)にフラグが立っている
+        self.state.stable_bitboard()
+    }
+
+    pub fn parity_bitboard(&self) -> BitBoard { // 空きマスが奇数個の領域にある空きマスにフラグが立っている
+        self.state.parity_bitboard()
     }
-}
\ No newline at end of file
+}
@@ -49,4 +49,11 @@ pub enum WinningStatus {
     Lose,
     Draw,
     NotFinished,
-}
\ No newline at end of file
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum BoundType {
+    Exact,
+    Lower,  // 真の値はscore以上 (beta cut)
+    Upper,  // 真の値はscore以下 (alphaを超えなかった)
+}
@@ -0,0 +1,212 @@
+use super::{action::Action, board::Board, bitboard::{BitBoard, BitBoardTrait}, constants::BOARD_SIZE, enums::Turn, state::State};
+
+const KILLER_NUM: usize = 2;
+const MAX_PLY: usize = 64;
+const PARITY_EMPTIES: i32 = 20;     // 空きマスがこれ以下のときだけ偶数理論で並べる
+
+// 各ヒューリスティックの有効/無効. 探索ノード数の比較用
+#[derive(Debug, Clone, Copy)]
+pub struct OrderingConfig {
+    pub hash_action:    bool,
+    pub mobility:   bool,
+    pub killer: bool,
+    pub history:    bool,
+    pub parity: bool,
+}
+
+impl OrderingConfig {
+    pub fn all() -> OrderingConfig {
+        OrderingConfig {
+            hash_action:    true,
+            mobility:   true,
+            killer: true,
+            history:    true,
+            parity: true,
+        }
+    }
+
+    pub fn none() -> OrderingConfig {
+        OrderingConfig {
+            hash_action:    false,
+            mobility:   false,
+            killer: false,
+            history:    false,
+            parity: false,
+        }
+    }
+}
+
+impl Default for OrderingConfig {
+    fn default() -> OrderingConfig {
+        OrderingConfig::all()
+    }
+}
+
+// first_actionがactionsに含まれていれば先頭に移す. 残りの順序は保つ
+pub fn order_action_first(actions: &mut [Action], first_action: Option<Action>) {
+    if let Some(first_action) = first_action {
+        if let Some(index) = actions.iter().position(|&action| action == first_action) {
+            actions[..=index].rotate_right(1);
+        }
+    }
+}
+
+// 置換表の最善手 > キラームーブ > 相手の着手可能数が少ない手 > 奇数領域の手 > history の順に並べる
+pub struct MoveOrderer {
+    pub config: OrderingConfig,
+    killers:    [[Option<Action>; KILLER_NUM]; MAX_PLY],
+    history:    [[u64; BOARD_SIZE]; 2],
+}
+
+impl MoveOrderer {
+    pub fn new(config: OrderingConfig) -> MoveOrderer {
+        MoveOrderer {
+            config,
+            killers:    [[None; KILLER_NUM]; MAX_PLY],
+            history:    [[0; BOARD_SIZE]; 2],
+        }
+    }
+
+    fn color_index(turn: Turn) -> usize {
+        if turn == Turn::White { 1 } else { 0 }
+    }
+
+    fn square_index(action: Action) -> usize {
+        action.bitboard.leading_zeros() as usize
+    }
+
+    fn opponent_mobility(state: &State, action: Action) -> i32 {
+        let mut next_state: State = *state;
+        let flips: BitBoard = state.flips(action);
+        next_state.make_move(action, flips);
+        next_state.legal_actions_bitboard().count()
+    }
+
+    fn key(&self, board: &Board, action: Action, hash_action: Option<Action>, parity_bitboard: BitBoard, ply: usize) -> i64 {
+        let mut key: i64 = 0;
+        if self.config.hash_action && hash_action == Some(action) {
+            key += 4 << 56;
+        }
+        if self.config.killer && ply < MAX_PLY {
+            for (i, killer) in self.killers[ply].iter().enumerate() {
+                if *killer == Some(action) {
+                    key += ((KILLER_NUM - i) as i64) << 56;
+                }
+            }
+        }
+        if parity_bitboard & action.bitboard != 0 {
+            key += 1 << 40;
+        }
+        if self.config.mobility {
+            key += ((BOARD_SIZE as i64) - MoveOrderer::opponent_mobility(&board.state, action) as i64) << 41;
+        }
+        if self.config.history {
+            let history: u64 = self.history[MoveOrderer::color_index(board.turn())][MoveOrderer::square_index(action)];
+            key += history.min((1 << 40) - 1) as i64;
+        }
+        key
+    }
+
+    pub fn order(&self, board: &Board, actions: &mut [Action], hash_action: Option<Action>, ply: usize) {
+        let use_parity: bool = self.config.parity && board.state.empty_bitboard().count() <= PARITY_EMPTIES;
+        let parity_bitboard: BitBoard = if use_parity { board.parity_bitboard() } else { 0 };
+        actions.sort_by_cached_key(|&action| -self.key(board, action, hash_action, parity_bitboard, ply));
+    }
+
+    // beta cutを起こした手を記録する
+    pub fn update_cutoff(&mut self, board: &Board, action: Action, depth: i32, ply: usize) {
+        if self.config.killer && ply < MAX_PLY && self.killers[ply][0] != Some(action) {
+            self.killers[ply].rotate_right(1);
+            self.killers[ply][0] = Some(action);
+        }
+        if self.config.history {
+            self.history[MoveOrderer::color_index(board.turn())][MoveOrderer::square_index(action)] += (depth * depth) as u64;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::{agents::{Agent, alphabeta::AlphaBetaAgent}, components::enums::BoardStatus, test_utils::random_board};
+
+    fn ordered(orderer: &MoveOrderer, board: &Board, hash_action: Option<Action>, ply: usize) -> Vec<Action> {
+        let mut actions: Vec<Action> = board.legal_actions();
+        orderer.order(board, &mut actions, hash_action, ply);
+        actions
+    }
+
+    #[test]
+    fn action_first_keeps_rest_order() {
+        let board: Board = Board::init();
+        let mut actions: Vec<Action> = board.legal_actions();
+        let expected: Vec<Action> = vec![actions[2], actions[0], actions[1], actions[3]];
+        order_action_first(&mut actions, Some(expected[0]));
+        assert_eq!(actions, expected);
+    }
+
+    // 初期局面の4手は相手の着手可能数が同じ
+    #[test]
+    fn hash_action_then_killer_then_history() {
+        let board: Board = Board::init();
+        let actions: Vec<Action> = board.legal_actions();
+        let mut orderer: MoveOrderer = MoveOrderer::new(OrderingConfig::all());
+        orderer.update_cutoff(&board, actions[3], 1, 0);    // ply 0のキラー
+        orderer.update_cutoff(&board, actions[2], 10, 1);   // 別のplyのキラーなのでhistoryだけ効く
+        assert_eq!(ordered(&orderer, &board, Some(actions[0]), 0), vec![actions[0], actions[3], actions[2], actions[1]]);
+        // 無効にしたヒューリスティックは使わない
+        assert_eq!(ordered(&MoveOrderer::new(OrderingConfig::none()), &board, Some(actions[3]), 0), actions);
+    }
+
+    // 相手の着手可能数が1違えば, historyの値がいくら大きくても着手可能数が少ない手を先にする
+    #[test]
+    fn mobility_outranks_history() {
+        let mut rng: StdRng = StdRng::seed_from_u64(0);
+        let mobility = |board: &Board, action: Action| MoveOrderer::opponent_mobility(&board.state, action);
+        let mut checked: bool = false;
+        for game in 0..20 {
+            let board: Board = random_board(8 + game, &mut rng);
+            let mut actions: Vec<Action> = board.legal_actions();
+            actions.sort_by_key(|&action| mobility(&board, action));
+            if actions.len() < 5 || mobility(&board, actions[0]) == mobility(&board, actions[2]) {
+                continue;
+            }
+            let (hash_action, killer, history_action) = (actions[actions.len() - 1], actions[actions.len() - 2], actions[2]);
+            let mut orderer: MoveOrderer = MoveOrderer::new(OrderingConfig::all());
+            orderer.update_cutoff(&board, killer, 1, 0);
+            for _ in 0..1000 {
+                orderer.update_cutoff(&board, history_action, 1000, 5);
+            }
+            let result: Vec<Action> = ordered(&orderer, &board, Some(hash_action), 0);
+            assert_eq!(&result[..2], &[hash_action, killer]);
+            assert!(result[2..].windows(2).all(|pair| mobility(&board, pair[0]) <= mobility(&board, pair[1])));
+            checked = true;
+        }
+        assert!(checked);
+    }
+
+    // 時間制限なしで同じ深さまで読むとき, 並べ替えを使うと探索ノード数は合計で多くならない
+    #[test]
+    fn ordering_reduces_nodes() {
+        const DEPTH: i32 = 4;
+        let mut rng: StdRng = StdRng::seed_from_u64(1);
+        let (mut all_nodes, mut none_nodes) = (0, 0);
+        for game in 0..8 {
+            let board: Board = random_board(6 + 3 * game, &mut rng);
+            if board.status() != BoardStatus::Usual {
+                continue;
+            }
+            for (ordering, nodes) in [(OrderingConfig::all(), &mut all_nodes), (OrderingConfig::none(), &mut none_nodes)] {
+                let mut agent: AlphaBetaAgent = AlphaBetaAgent::new(DEPTH, 0);
+                agent.time_limit = None;
+                agent.ordering = ordering;
+                agent.next_action(&board);
+                assert_eq!(agent.search_info().depth, DEPTH);
+                *nodes += agent.search_info().nodes;
+            }
+        }
+        assert!(all_nodes <= none_nodes, "all {} none {}", all_nodes, none_nodes);
+    }
+}
@@ -1,7 +1,7 @@
-use super::{bitboard::BitBoard, constants::{FIRST_WHITE_BIT, FIRST_BLACK_BIT, TOP_BIT, BOARD_LEN}, enums::{Turn, FIRST_TURN}};
+use super::{action::Action, bitboard::{BitBoard, BitBoardTrait, SYMMETRY_NUM}, constants::{FIRST_WHITE_BIT, FIRST_BLACK_BIT, TOP_BIT, BOARD_LEN}, enums::{Turn, TurnTrait, FIRST_TURN}, zobrist::{self, HashType}};
 use std::io;
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub struct State {
     pub player_bit:  BitBoard,
     pub opponent_bit: BitBoard,
@@ -48,3 +48,358 @@ impl State {
         }
     }
 }
+
+impl State {
+    // turnは手番側(player_bit)の色
+    pub fn hash(&self, turn: Turn) -> HashType {
+        zobrist::bitboard_key(self.player_bit, turn)
+            ^ zobrist::bitboard_key(self.opponent_bit, turn.reverse())
+            ^ zobrist::side_key(turn)
+    }
+
+    // make_move前のhashとturnから, make_move後のハッシュを差分計算する
+    pub fn hash_after_move(hash: HashType, turn: Turn, action: Action, flips: BitBoard) -> HashType {
+        hash ^ zobrist::move_key(turn, action, flips)
+    }
+
+    pub fn hash_after_pass(hash: HashType) -> HashType {
+        hash ^ zobrist::pass_key()
+    }
+
+    pub fn symmetry(&self, index: usize) -> State {
+        State::build(self.player_bit.symmetry(index), self.opponent_bit.symmetry(index))
+    }
+
+    // 8通りの対称変換のうち最小のハッシュ. 対称な局面同士で一致する
+    pub fn canonical_hash(&self, turn: Turn) -> HashType {
+        (0..SYMMETRY_NUM)
+            .map(|index| self.symmetry(index).hash(turn))
+            .min()
+            .unwrap()
+    }
+}
+
+// legal_actions_bitboardのみで使う.高速化のために外に出しておく(効果未検証)
+struct Direction {
+    watch_board: BitBoard,
+    shift_step: i32,
+}
+
+impl State {
+    fn transfer(bit: BitBoard, k: i32) -> BitBoard {
+        match k {
+            0 => (bit << 8) & 0xffffffffffffff00, // 上
+            1 => (bit << 7) & 0x7f7f7f7f7f7f7f00, // 右上
+            2 => (bit >> 1) & 0x7f7f7f7f7f7f7f7f, // 右
+            3 => (bit >> 9) & 0x007f7f7f7f7f7f7f, // 右下
+            4 => (bit >> 8) & 0x00ffffffffffffff, // 下
+            5 => (bit >> 7) & 0x00fefefefefefefe, // 左下
+            6 => (bit << 1) & 0xfefefefefefefefe, // 左
+            7 => (bit << 9) & 0xfefefefefefefe00, // 左上
+            _ => panic!("undefined k was given in State.transfer: k is {}", k)
+        }
+    }
+
+    fn neighbors(bit: BitBoard) -> BitBoard { // bitの8近傍にフラグが立っている
+        (0..8).fold(0, |res, k| res | State::transfer(bit, k))
+    }
+
+    pub fn empty_bitboard(&self) -> BitBoard {
+        !(self.player_bit | self.opponent_bit)
+    }
+
+    pub fn potential_actions_bitboard(&self) -> BitBoard { // 相手の石に隣接する空きマスにフラグが立っている
+        self.empty_bitboard() & State::neighbors(self.opponent_bit)
+    }
+
+    pub fn frontier_bitboard(&self) -> BitBoard { // 空きマスに隣接する石(両者)にフラグが立っている
+        !self.empty_bitboard() & State::neighbors(self.empty_bitboard())
+    }
+
+    // bitの石のうち, 今後どのように打たれても裏返らない石を求める(保守的な近似)
+    // 4方向の軸それぞれについて, 軸上が埋まっているか, 片側が盤外か同色の確定石なら確定とする
+    fn stable_bitboard_of(bit: BitBoard, filled_lines: &[BitBoard; 4]) -> BitBoard {
+        let mut stable: BitBoard = 0;
+        loop {
+            let mut next_stable: BitBoard = bit;
+            for (k, filled_line) in filled_lines.iter().enumerate() {
+                // 方向kの隣が盤外か確定石, または方向k+4の隣が盤外か確定石
+                let forward: BitBoard = !State::transfer(!0, k as i32 + 4) | State::transfer(stable, k as i32 + 4);
+                let backward: BitBoard = !State::transfer(!0, k as i32) | State::transfer(stable, k as i32);
+                next_stable &= filled_line | forward | backward;
+            }
+            if next_stable == stable {
+                return stable;
+            }
+            stable = next_stable;
+        }
+    }
+
+    pub fn stable_bitboard(&self) -> BitBoard { // 確定石(両者)にフラグが立っている
+        let filled: BitBoard = self.player_bit | self.opponent_bit;
+        let mut filled_lines: [BitBoard; 4] = [0; 4];
+        for (k, filled_line) in filled_lines.iter_mut().enumerate() {
+            // 方向kと方向k+4の端まで空きマスがないマス
+            let mut forward: BitBoard = filled;
+            let mut backward: BitBoard = filled;
+            for _ in 0..7 {
+                forward &= State::transfer(forward, k as i32 + 4) | !State::transfer(!0, k as i32 + 4);
+                backward &= State::transfer(backward, k as i32) | !State::transfer(!0, k as i32);
+            }
+            *filled_line = forward & backward;
+        }
+        State::stable_bitboard_of(self.player_bit, &filled_lines) | State::stable_bitboard_of(self.opponent_bit, &filled_lines)
+    }
+
+    pub fn empty_regions(&self) -> Vec<BitBoard> { // 空きマスを8近傍でつながった領域に分ける
+        let mut regions: Vec<BitBoard> = Vec::new();
+        let mut rest: BitBoard = self.empty_bitboard();
+        while rest != 0 {
+            let mut region: BitBoard = TOP_BIT >> rest.leading_zeros();
+            loop {
+                let next_region: BitBoard = region | (State::neighbors(region) & rest);
+                if next_region == region {
+                    break;
+                }
+                region = next_region;
+            }
+            regions.push(region);
+            rest &= !region;
+        }
+        regions
+    }
+
+    pub fn parity_bitboard(&self) -> BitBoard { // 空きマスが奇数個の領域にある空きマスにフラグが立っている
+        self.empty_regions().into_iter()
+            .filter(|region| region.count() % 2 == 1)
+            .fold(0, |res, region| res | region)
+    }
+
+    pub fn flips(&self, action: Action) -> BitBoard { // actionを打ったときに裏返る石にフラグが立っている
+        let mut reverse_board: BitBoard = 0;
+        for k in 0..8 {
+            let mut tmp_reverse_board: BitBoard = 0;
+            let mut mask: BitBoard = State::transfer(action.bitboard, k);
+            while mask != 0 && (mask & self.opponent_bit) != 0 {
+                tmp_reverse_board |= mask;
+                mask = State::transfer(mask, k);
+            }
+            if mask & self.player_bit != 0 {
+                reverse_board |= tmp_reverse_board;
+            }
+        }
+        reverse_board
+    }
+
+    // flipsには同じ局面でのself.flips(action)の結果を渡す
+    pub fn make_move(&mut self, action: Action, flips: BitBoard) {
+        let player_bit: BitBoard = self.player_bit;
+        self.player_bit = self.opponent_bit ^ flips;
+        self.opponent_bit = player_bit ^ (action.bitboard | flips);
+    }
+
+    pub fn undo_move(&mut self, action: Action, flips: BitBoard) {
+        let player_bit: BitBoard = self.player_bit;
+        self.player_bit = self.opponent_bit ^ (action.bitboard | flips);
+        self.opponent_bit = player_bit ^ flips;
+    }
+
+    pub fn make_pass(&mut self) {
+        std::mem::swap(&mut self.player_bit, &mut self.opponent_bit);
+    }
+
+    pub fn legal_actions_bitboard(&self) -> BitBoard { // 着手可能なマスにフラグが立っている
+        let horizontal_watch_board: BitBoard = self.opponent_bit & 0x7e7e7e7e7e7e7e7e;
+        let vertical_watch_board: BitBoard = self.opponent_bit & 0x00FFFFFFFFFFFF00;
+        let all_side_watch_board: BitBoard = self.opponent_bit & 0x007e7e7e7e7e7e00;
+
+        let blank_board: BitBoard = !(self.player_bit | self.opponent_bit);
+
+        let mut legal_board: BitBoard = 0;
+        let directions: [Direction; 4] = [
+            Direction {
+                watch_board: horizontal_watch_board,
+                shift_step: 1,
+            },
+            Direction {
+                watch_board: vertical_watch_board,
+                shift_step: 8,
+            },
+            Direction {
+                watch_board: all_side_watch_board,
+                shift_step: 7,
+            },
+            Direction {
+                watch_board: all_side_watch_board,
+                shift_step: 9,
+            },
+        ];
+
+        for dir in directions.iter() {
+            let mut tmp_board: BitBoard;
+
+            tmp_board = dir.watch_board & (self.player_bit << dir.shift_step);
+            for _ in 0..5 {
+                tmp_board |= dir.watch_board & (tmp_board << dir.shift_step);
+            }
+            legal_board |= blank_board & (tmp_board << dir.shift_step);
+
+            tmp_board = dir.watch_board & (self.player_bit >> dir.shift_step);
+            for _ in 0..5 {
+                tmp_board |= dir.watch_board & (tmp_board >> dir.shift_step);
+            }
+            legal_board |= blank_board & (tmp_board >> dir.shift_step);
+        }
+        legal_board
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::test_utils::random_action;
+
+    #[test]
+    fn incremental_hash_matches_full_hash() {
+        let mut rng: StdRng = StdRng::seed_from_u64(0);
+        for _ in 0..20 {
+            let mut state: State = State::init();
+            let mut turn: Turn = FIRST_TURN;
+            let mut hash: HashType = state.hash(turn);
+            let mut passed: bool = false;
+            loop {
+                match random_action(&state, &mut rng) {
+                    Some(action) => {
+                        let flips: BitBoard = state.flips(action);
+                        hash = State::hash_after_move(hash, turn, action, flips);
+                        state.make_move(action, flips);
+                        passed = false;
+                    },
+                    None if passed => break,
+                    None => {
+                        hash = State::hash_after_pass(hash);
+                        state.make_pass();
+                        passed = true;
+                    },
+                }
+                turn = turn.reverse();
+                assert_eq!(hash, state.hash(turn));
+            }
+        }
+    }
+
+    #[test]
+    fn canonical_hash_is_symmetric() {
+        let mut rng: StdRng = StdRng::seed_from_u64(1);
+        let mut state: State = State::init();
+        let mut turn: Turn = FIRST_TURN;
+        while let Some(action) = random_action(&state, &mut rng) {
+            state.make_move(action, state.flips(action));
+            turn = turn.reverse();
+            let canonical_hash: HashType = state.canonical_hash(turn);
+            for index in 0..SYMMETRY_NUM {
+                assert_eq!(state.symmetry(index).canonical_hash(turn), canonical_hash);
+            }
+        }
+    }
+
+    #[test]
+    fn stable_discs_of_simple_positions() {
+        assert_eq!(State::build(0x00ff00ff00ff00ff, 0xff00ff00ff00ff00).stable_bitboard(), !0);
+        assert_eq!(State::init().stable_bitboard(), 0);
+        assert_eq!(State::build(TOP_BIT, FIRST_WHITE_BIT).stable_bitboard(), TOP_BIT);
+    }
+
+    // 確定石とされた石は, その後どう打っても裏返らない
+    #[test]
+    fn stable_discs_are_never_flipped() {
+        let mut rng: StdRng = StdRng::seed_from_u64(2);
+        for _ in 0..200 {
+            // (黒, 白)の石と, その局面で確定石とされた石
+            let mut history: Vec<(BitBoard, BitBoard, BitBoard)> = Vec::new();
+            let mut state: State = State::init();
+            let mut turn: Turn = FIRST_TURN;
+            let mut passed: bool = false;
+            loop {
+                let (black_bit, white_bit) = if turn == Turn::Black { (state.player_bit, state.opponent_bit) } else { (state.opponent_bit, state.player_bit) };
+                let stable_bit: BitBoard = state.stable_bitboard();
+                assert_eq!(stable_bit & state.empty_bitboard(), 0);
+                if state.empty_bitboard() == 0 {
+                    assert_eq!(stable_bit, !0);
+                }
+                for &(prev_black_bit, prev_white_bit, prev_stable_bit) in history.iter() {
+                    assert_eq!(prev_stable_bit & prev_black_bit & !black_bit, 0);
+                    assert_eq!(prev_stable_bit & prev_white_bit & !white_bit, 0);
+                }
+                history.push((black_bit, white_bit, stable_bit));
+
+                match random_action(&state, &mut rng) {
+                    Some(action) => {
+                        state.make_move(action, state.flips(action));
+                        passed = false;
+                    },
+                    None if passed => break,
+                    None => {
+                        state.make_pass();
+                        passed = true;
+                    },
+                }
+                turn = turn.reverse();
+            }
+        }
+    }
+    const A1: BitBoard = TOP_BIT;
+    const H1: BitBoard = TOP_BIT >> 7;
+    const A2: BitBoard = TOP_BIT >> 8;
+    const B2: BitBoard = TOP_BIT >> 9;
+    const D4: BitBoard = TOP_BIT >> 27;
+    const A8: BitBoard = TOP_BIT >> 56;
+    const B8: BitBoard = TOP_BIT >> 57;
+    const H8: BitBoard = TOP_BIT >> 63;
+
+    // empty_bit以外のマスはすべて手番側の石
+    fn state_with_empties(empty_bit: BitBoard) -> State {
+        State::build(!empty_bit, 0)
+    }
+
+    fn sorted_regions(state: &State) -> Vec<BitBoard> {
+        let mut regions: Vec<BitBoard> = state.empty_regions();
+        regions.sort_unstable();
+        regions
+    }
+
+    #[test]
+    fn corners_are_separate_regions() {
+        let state: State = state_with_empties(A1 | H1 | A8 | B8 | H8);
+        assert_eq!(sorted_regions(&state), vec![H8, A8 | B8, H1, A1]);
+        assert_eq!(state.parity_bitboard(), A1 | H1 | H8);
+    }
+
+    // 斜めだけで接していても同じ領域. 盤の端をまたいではつながらない
+    #[test]
+    fn diagonal_contact_joins_regions() {
+        let state: State = state_with_empties(A1 | B2 | D4);
+        assert_eq!(sorted_regions(&state), vec![D4, A1 | B2]);
+        assert_eq!(state.parity_bitboard(), D4);
+
+        let state: State = state_with_empties(H1 | A2);
+        assert_eq!(sorted_regions(&state), vec![A2, H1]);
+        assert_eq!(state.parity_bitboard(), H1 | A2);
+    }
+
+    #[test]
+    fn full_board_has_no_regions() {
+        let state: State = State::build(0x00ff00ff00ff00ff, 0xff00ff00ff00ff00);
+        assert!(state.empty_regions().is_empty());
+        assert_eq!(state.parity_bitboard(), 0);
+    }
+
+    #[test]
+    fn initial_board_is_one_even_region() {
+        let state: State = State::init();
+        assert_eq!(state.empty_regions(), vec![state.empty_bitboard()]);
+        assert_eq!(state.parity_bitboard(), 0);
+    }
+}
@@ -0,0 +1,186 @@
+use std::mem;
+
+use super::{action::Action, board::Board, constants::ScoreType, enums::{BoundType, BoardStatus}, zobrist::HashType};
+
+pub const DEFAULT_TABLE_SIZE: usize = 1 << 16;
+
+#[derive(Debug, Clone, Copy)]
+pub struct TableEntry {
+    pub hash:   HashType,
+    pub depth:  i32,
+    pub score:  ScoreType,
+    pub bound:  BoundType,
+    pub best_action:    Option<Action>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TableStats {
+    pub probes: u64,
+    pub hits:   u64,
+    pub stores: u64,
+    pub replacements:   u64,    // 別の局面のエントリを上書きした回数
+}
+
+impl TableStats {
+    pub fn hit_rate(&self) -> f64 {
+        if self.probes == 0 {
+            0.
+        } else {
+            self.hits as f64 / self.probes as f64
+        }
+    }
+}
+
+// 固定サイズの置換表. 衝突時は探索深さが深い方を残す
+pub struct TranspositionTable {
+    entries:    Vec<Option<TableEntry>>,
+    mask:   usize,
+    stats:  TableStats,
+}
+
+impl TranspositionTable {
+    // sizeは2の冪に切り下げる
+    pub fn new(size: usize) -> TranspositionTable {
+        assert!(size > 0, "Can't create TranspositionTable whose size is 0");
+        let size: usize = 1 << (usize::BITS - 1 - size.leading_zeros());
+        TranspositionTable {
+            entries:    vec![None; size],
+            mask:   size - 1,
+            stats:  TableStats::default(),
+        }
+    }
+
+    // 使用メモリ(byte)がmemory_bytes以下になる最大のサイズで作る
+    pub fn with_memory(memory_bytes: usize) -> TranspositionTable {
+        TranspositionTable::new(memory_bytes / mem::size_of::<Option<TableEntry>>())
+    }
+
+    pub fn size(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn memory_bytes(&self) -> usize {
+        self.entries.len() * mem::size_of::<Option<TableEntry>>()
+    }
+
+    pub fn stats(&self) -> TableStats {
+        self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = TableStats::default();
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.iter_mut().for_each(|entry| *entry = None);
+        self.reset_stats();
+    }
+
+    pub fn probe(&mut self, hash: HashType) -> Option<TableEntry> {
+        self.stats.probes += 1;
+        match self.entries[hash as usize & self.mask] {
+            Some(entry) if entry.hash == hash => {
+                self.stats.hits += 1;
+                Some(entry)
+            },
+            _ => None,
+        }
+    }
+
+    // 統計に数えない参照. PVの取り出しなど探索外での利用向け
+    pub fn peek(&self, hash: HashType) -> Option<TableEntry> {
+        match self.entries[hash as usize & self.mask] {
+            Some(entry) if entry.hash == hash => Some(entry),
+            _ => None,
+        }
+    }
+
+    pub fn store(&mut self, hash: HashType, depth: i32, score: ScoreType, bound: BoundType, best_action: Option<Action>) {
+        let slot: &mut Option<TableEntry> = &mut self.entries[hash as usize & self.mask];
+        if let Some(entry) = slot {
+            if entry.hash != hash {
+                if entry.depth > depth {
+                    return;
+                }
+                self.stats.replacements += 1;
+            }
+        }
+        *slot = Some(TableEntry {
+            hash,
+            depth,
+            score,
+            bound,
+            best_action,
+        });
+        self.stats.stores += 1;
+    }
+
+    // first_actionから置換表の最善手を辿って読み筋を取り出す. パスは読み筋に含めない
+    pub fn principal_variation(&self, board: &Board, first_action: Action, max_len: i32) -> Vec<Action> {
+        let mut tmp_board: Board = board.play_onestep(first_action);
+        let mut pv: Vec<Action> = vec![first_action];
+        while (pv.len() as i32) < max_len {
+            match tmp_board.status() {
+                BoardStatus::Finished => { break; },
+                BoardStatus::Pass => { tmp_board.make_pass(); },
+                BoardStatus::Usual => {},
+            }
+            let Some(action) = self.peek(tmp_board.hash()).and_then(|entry| entry.best_action) else { break; };
+            if tmp_board.legal_actions_bitboard() & action.bitboard == 0 {
+                break;
+            }
+            tmp_board = tmp_board.play_onestep(action);
+            pv.push(action);
+        }
+        pv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // サイズ4なので1と5は同じスロットに入る
+    #[test]
+    fn deeper_entry_is_kept() {
+        let mut table: TranspositionTable = TranspositionTable::new(5);
+        assert_eq!(table.size(), 4);
+        table.store(1, 5, 10, BoundType::Exact, None);
+        table.store(5, 3, 20, BoundType::Lower, None);
+        assert_eq!(table.peek(1).map(|entry| (entry.depth, entry.score)), Some((5, 10)));
+        assert!(table.peek(5).is_none());
+        assert_eq!((table.stats().stores, table.stats().replacements), (1, 0));
+
+        // 同じ深さなら新しい局面で上書きする
+        table.store(5, 5, 20, BoundType::Lower, None);
+        assert!(table.peek(1).is_none());
+        assert_eq!(table.peek(5).map(|entry| (entry.depth, entry.bound)), Some((5, BoundType::Lower)));
+        assert_eq!((table.stats().stores, table.stats().replacements), (2, 1));
+
+        // 同じ局面は浅くても上書きし, 置き換えには数えない
+        table.store(5, 1, 30, BoundType::Upper, None);
+        assert_eq!(table.peek(5).map(|entry| (entry.depth, entry.score)), Some((1, 30)));
+        assert_eq!((table.stats().stores, table.stats().replacements), (3, 1));
+    }
+
+    #[test]
+    fn probe_counts_and_peek_does_not() {
+        let mut table: TranspositionTable = TranspositionTable::new(4);
+        table.store(1, 2, 10, BoundType::Exact, None);
+        assert!(table.probe(1).is_some());
+        assert!(table.probe(5).is_none());  // 同じスロットの別の局面
+        assert!(table.probe(2).is_none());  // 空のスロット
+        assert!(table.peek(1).is_some());
+        assert!(table.peek(5).is_none());
+        let stats: TableStats = table.stats();
+        assert_eq!((stats.probes, stats.hits), (3, 1));
+        assert!((stats.hit_rate() - 1. / 3.).abs() < 1e-12);
+
+        table.reset_stats();
+        assert_eq!(table.stats().probes, 0);
+        assert_eq!(table.stats().hit_rate(), 0.);
+        assert!(table.peek(1).is_some());
+        table.clear();
+        assert!(table.peek(1).is_none());
+    }
+}
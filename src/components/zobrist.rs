@@ -0,0 +1,61 @@
+use super::{action::Action, bitboard::BitBoard, constants::{TOP_BIT, BOARD_SIZE}, enums::Turn};
+
+pub type HashType = u64;
+
+// 盤面の各マスに色ごとの乱数を割り当てる. 定数として埋め込むため乱数はsplitmix64で生成する
+const fn splitmix64(seed: u64) -> u64 {
+    let mut z: u64 = seed.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+const fn build_table() -> [[HashType; BOARD_SIZE]; 2] {
+    let mut table: [[HashType; BOARD_SIZE]; 2] = [[0; BOARD_SIZE]; 2];
+    let mut seed: u64 = 0x2545f4914f6cdd1d;
+    let mut color = 0;
+    while color < 2 {
+        let mut i = 0;
+        while i < BOARD_SIZE {
+            seed = splitmix64(seed);
+            table[color][i] = seed;
+            i += 1;
+        }
+        color += 1;
+    }
+    table
+}
+
+const ZOBRIST_TABLE: [[HashType; BOARD_SIZE]; 2] = build_table();
+const SIDE_KEY: HashType = splitmix64(0x6a09e667f3bcc908);
+
+fn color_index(turn: Turn) -> usize {
+    if turn == Turn::White { 1 } else { 0 }
+}
+
+// bitboardの立っているビットに対応する乱数を全てxorする
+pub fn bitboard_key(bitboard: BitBoard, turn: Turn) -> HashType {
+    let table: &[HashType; BOARD_SIZE] = &ZOBRIST_TABLE[color_index(turn)];
+    let mut rest: BitBoard = bitboard;
+    let mut key: HashType = 0;
+    while rest != 0 {
+        let i: usize = rest.leading_zeros() as usize;
+        key ^= table[i];
+        rest &= !(TOP_BIT >> i);
+    }
+    key
+}
+
+pub fn side_key(turn: Turn) -> HashType {
+    if turn == Turn::White { SIDE_KEY } else { 0 }
+}
+
+// turn側がactionを打ってflipsが裏返ったときのハッシュの差分
+pub fn move_key(turn: Turn, action: Action, flips: BitBoard) -> HashType {
+    let opponent: Turn = if turn == Turn::White { Turn::Black } else { Turn::White };
+    bitboard_key(action.bitboard | flips, turn) ^ bitboard_key(flips, opponent) ^ SIDE_KEY
+}
+
+pub fn pass_key() -> HashType {
+    SIDE_KEY
+}
@@ -1,7 +1,17 @@
 use crate::components::{board::Board, constants::ScoreType};
 
 pub mod cell_score;
+pub mod disc_count;
+pub mod mobility;
+pub mod stability;
+pub mod parity;
+pub mod phase;
+pub mod pattern;
+pub mod mlp;
+pub mod train;
+mod weight_file;
 
-pub trait EvalTrait {
-    fn eval(board: Board) -> ScoreType;
-}
\ No newline at end of file
+// 手番側(board.state.player_bit)から見た評価値を返す
+pub trait EvalTrait: Send + Sync {
+    fn eval(&self, board: &Board) -> ScoreType;
+}
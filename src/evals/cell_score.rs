@@ -2,32 +2,48 @@ use crate::components::{board::Board, constants::{ScoreType, TOP_BIT, BOARD_SIZE
 
 use super::EvalTrait;
 
-pub struct CellEval {}
+pub const DEFAULT_SCORE_TABLE: [ScoreType; BOARD_SIZE] = [
+    30, -12, 0, -1, -1, 0, -12, 30,
+    -12, -15, -3, -3, -3, -3, -15, -12,
+    0, -3, 0, -1, -1, 0, -3, 0,
+    -1, -3, -1, -1, -1, -1, -3, -1,
+    -1, -3, -1, -1, -1, -1, -3, -1,
+    0, -3, 0, -1, -1, 0, -3, 0,
+    -12, -15, -3, -3, -3, -3, -15, -12,
+    30, -12, 0, -1, -1, 0, -12, 30
+];
+
+pub struct CellEval {
+    pub score_table:    [ScoreType; BOARD_SIZE],
+}
+
+impl CellEval {
+    pub fn new(score_table: [ScoreType; BOARD_SIZE]) -> CellEval {
+        CellEval {
+            score_table,
+        }
+    }
+}
+
+impl Default for CellEval {
+    fn default() -> CellEval {
+        CellEval::new(DEFAULT_SCORE_TABLE)
+    }
+}
 
 impl EvalTrait for CellEval {
-    fn eval(board: Board) -> ScoreType {
+    fn eval(&self, board: &Board) -> ScoreType {
         let mut res_score: ScoreType = 0;
-        const SCORE_TABLE: [ScoreType; BOARD_SIZE] = [
-            30, -12, 0, -1, -1, 0, -12, 30,
-            -12, -15, -3, -3, -3, -3, -15, -12,
-            0, -3, 0, -1, -1, 0, -3, 0,
-            -1, -3, -1, -1, -1, -1, -3, -1,
-            -1, -3, -1, -1, -1, -1, -3, -1,
-            0, -3, 0, -1, -1, 0, -3, 0,
-            -12, -15, -3, -3, -3, -3, -15, -12,
-            30, -12, 0, -1, -1, 0, -12, 30
-        ];
-
         let mut mask: BitBoard = TOP_BIT;
-        for i in 0..BOARD_SIZE {
+        for score in self.score_table.iter() {
             if board.state.player_bit & mask != 0 {
-                res_score += SCORE_TABLE[i];
+                res_score += score;
             }
             if board.state.opponent_bit & mask != 0 {
-                res_score -= SCORE_TABLE[i];
+                res_score -= score;
             }
             mask >>= 1;
         }
         res_score
     }
-}
\ No newline at end of file
+}
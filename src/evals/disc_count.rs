@@ -0,0 +1,12 @@
+use crate::components::{board::Board, bitboard::BitBoardTrait, constants::ScoreType};
+
+use super::EvalTrait;
+
+// 石数の差(自分 - 相手)
+pub struct DiscCountEval {}
+
+impl EvalTrait for DiscCountEval {
+    fn eval(&self, board: &Board) -> ScoreType {
+        board.state.player_bit.count() - board.state.opponent_bit.count()
+    }
+}
@@ -0,0 +1,228 @@
+use std::{fs, io, path::Path};
+
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+use crate::{components::{board::Board, bitboard::{BitBoard, BitBoardTrait}, constants::{ScoreType, TOP_BIT, BOARD_SIZE}, state::State}, math::{functions::Activation, matrix::{Matrix, NumType, Vector}}};
+
+use super::{EvalTrait, weight_file::WeightTokens};
+
+const MOBILITY_SCALE: NumType = 1. / 16.;  // 着手可能数の特徴量を他の0/1の特徴量と同程度の大きさにする
+const MAX_LAYER_NUM: usize = 64;        // 重みファイルのヘッダの上限. 壊れたファイルで巨大な確保をしないようにする
+const MAX_LAYER_SIZE: usize = 1 << 16;
+
+// 全結合層. weightは(出力数)x(入力数)
+#[derive(Debug, Clone)]
+pub struct DenseLayer {
+    pub weight: Matrix,
+    pub bias:   Vector,
+    pub activation: Activation,
+}
+
+impl DenseLayer {
+    pub fn forward(&self, input: &Vector) -> Vector {
+        let activation: Activation = self.activation;
+        (&self.weight.mul_vec(input) + &self.bias).map(|x| activation.forward(x))
+    }
+}
+
+// 特徴量: 手番側の石(64) + 相手の石(64) + (use_mobilityなら)両者の着手可能数(2)
+// 最後の層の出力(1つ)にoutput_scaleを掛けて評価値にする
+pub struct MlpEval {
+    layers: Vec<DenseLayer>,
+    first_weight_t: Matrix,     // 1層目の重みの転置. 石のある特徴量の行だけを足して1層目を計算する
+    pub use_mobility:   bool,
+    pub output_scale:   NumType,
+}
+
+impl MlpEval {
+    pub fn new(layers: Vec<DenseLayer>, use_mobility: bool, output_scale: NumType) -> MlpEval {
+        let mut input_num: usize = MlpEval::feature_num(use_mobility);
+        for layer in layers.iter() {
+            assert_eq!(layer.weight.cols(), input_num, "layer input size does not match");
+            assert_eq!(layer.weight.rows(), layer.bias.rows, "layer bias size does not match");
+            input_num = layer.weight.rows();
+        }
+        assert_eq!(input_num, 1, "the last layer must have one output");
+        MlpEval {
+            first_weight_t: layers[0].weight.t(),
+            layers,
+            use_mobility,
+            output_scale,
+        }
+    }
+
+    // hidden_sizesの各層はactivation, 出力層は恒等関数. 重みは一様乱数で初期化する(Xavier)
+    pub fn random(hidden_sizes: &[usize], activation: Activation, use_mobility: bool, output_scale: NumType, seed: u64) -> MlpEval {
+        let mut rng: StdRng = StdRng::seed_from_u64(seed);
+        let mut sizes: Vec<usize> = vec![MlpEval::feature_num(use_mobility)];
+        sizes.extend_from_slice(hidden_sizes);
+        sizes.push(1);
+
+        let layers: Vec<DenseLayer> = sizes.windows(2).enumerate().map(|(i, size)| {
+            let (input_num, output_num): (usize, usize) = (size[0], size[1]);
+            let limit: NumType = (6. / (input_num + output_num) as NumType).sqrt();
+            DenseLayer {
+                weight: Matrix::from_flat(output_num, input_num, (0..output_num * input_num).map(|_| rng.gen_range(-limit..limit)).collect()),
+                bias:   Vector::zeros(output_num),
+                activation: if i + 2 == sizes.len() { Activation::Identity } else { activation },
+            }
+        }).collect();
+        MlpEval::new(layers, use_mobility, output_scale)
+    }
+
+    pub fn layers(&self) -> &[DenseLayer] {
+        &self.layers
+    }
+
+    pub fn feature_num(use_mobility: bool) -> usize {
+        2 * BOARD_SIZE + if use_mobility { 2 } else { 0 }
+    }
+
+    pub fn features(&self, state: &State) -> Vector {
+        let mut data: Vec<NumType> = Vec::with_capacity(MlpEval::feature_num(self.use_mobility));
+        for bit in [state.player_bit, state.opponent_bit] {
+            let mut mask: BitBoard = TOP_BIT;
+            for _ in 0..BOARD_SIZE {
+                data.push(if bit & mask != 0 { 1. } else { 0. });
+                mask >>= 1;
+            }
+        }
+        if self.use_mobility {
+            let opponent_state: State = State::build(state.opponent_bit, state.player_bit);
+            data.push(state.legal_actions_bitboard().count() as NumType * MOBILITY_SCALE);
+            data.push(opponent_state.legal_actions_bitboard().count() as NumType * MOBILITY_SCALE);
+        }
+        Vector::new(data)
+    }
+
+    // 特徴量はほとんどが0なので, 1層目は値が0でない特徴量の重みだけを足す
+    fn first_layer(&self, features: &Vector) -> Vector {
+        let first_layer: &DenseLayer = &self.layers[0];
+        let mut hidden: Vector = first_layer.bias.clone();
+        for (i, &x) in features.data.iter().enumerate() {
+            if x != 0. {
+                for (h, &w) in hidden.data.iter_mut().zip(self.first_weight_t.row(i)) {
+                    *h += w * x;
+                }
+            }
+        }
+        let activation: Activation = first_layer.activation;
+        hidden.map(|x| activation.forward(x))
+    }
+
+    pub fn raw_eval(&self, state: &State) -> NumType {
+        let hidden: Vector = self.first_layer(&self.features(state));
+        let output: Vector = self.layers[1..].iter().fold(hidden, |x, layer| layer.forward(&x));
+        output.data[0]
+    }
+
+    // 形式: "mobility <0|1>", "scale <値>", "layers <層数>"の後に
+    // 各層について"layer <入力数> <出力数> <活性化関数>", 重み(行優先), バイアスを空白区切りで並べる
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<MlpEval> {
+        let mut tokens: WeightTokens = WeightTokens::read(path)?;
+        tokens.expect("mobility")?;
+        let use_mobility: bool = tokens.parse::<u8>()? != 0;
+        tokens.expect("scale")?;
+        let output_scale: NumType = tokens.parse()?;
+        tokens.expect("layers")?;
+        let layer_num: usize = tokens.parse()?;
+        if layer_num == 0 || layer_num > MAX_LAYER_NUM {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("layer count must be in 1..={}, but {} was given", MAX_LAYER_NUM, layer_num)));
+        }
+
+        let mut input_num: usize = MlpEval::feature_num(use_mobility);
+        let mut layers: Vec<DenseLayer> = Vec::new();
+        for _ in 0..layer_num {
+            tokens.expect("layer")?;
+            let layer_input_num: usize = tokens.parse()?;
+            let output_num: usize = tokens.parse()?;
+            let name: String = tokens.parse()?;
+            if layer_input_num != input_num {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("layer needs {} inputs, but {} was given", input_num, layer_input_num)));
+            }
+            if output_num == 0 || output_num > MAX_LAYER_SIZE {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("layer output size must be in 1..={}, but {} was given", MAX_LAYER_SIZE, output_num)));
+            }
+            let weight_num: usize = output_num.checked_mul(input_num)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "layer is too large"))?;
+            let activation: Activation = Activation::from_name(&name)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("unknown activation: {}", name)))?;
+            let weight: Vec<NumType> = (0..weight_num).map(|_| tokens.parse()).collect::<io::Result<_>>()?;
+            let bias: Vec<NumType> = (0..output_num).map(|_| tokens.parse()).collect::<io::Result<_>>()?;
+            layers.push(DenseLayer {
+                weight: Matrix::from_flat(output_num, input_num, weight),
+                bias:   Vector::new(bias),
+                activation,
+            });
+            input_num = output_num;
+        }
+        if input_num != 1 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "the last layer must have one output"));
+        }
+        Ok(MlpEval::new(layers, use_mobility, output_scale))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut text: String = format!("mobility {}\nscale {}\nlayers {}\n", self.use_mobility as u8, self.output_scale, self.layers.len());
+        for layer in self.layers.iter() {
+            text += &format!("layer {} {} {}\n", layer.weight.cols(), layer.weight.rows(), layer.activation.name());
+            for values in [layer.weight.as_slice(), &layer.bias.data] {
+                let line: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+                text += &line.join(" ");
+                text += "\n";
+            }
+        }
+        fs::write(path, text)
+    }
+}
+
+impl EvalTrait for MlpEval {
+    fn eval(&self, board: &Board) -> ScoreType {
+        (self.raw_eval(&board.state) * self.output_scale).round() as ScoreType
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::with_temp_file;
+
+    #[test]
+    fn first_layer_matches_dense_forward() {
+        let eval: MlpEval = MlpEval::random(&[16], Activation::Tanh, true, 1., 0);
+        for state in [State::init(), State::build(0x00ff00ff00ff00ff, 0xff00ff0000000000)] {
+            let features: Vector = eval.features(&state);
+            let sparse: Vector = eval.first_layer(&features);
+            let dense: Vector = eval.layers()[0].forward(&features);
+            for (a, b) in sparse.data.iter().zip(&dense.data) {
+                assert!((a - b).abs() < 1e-5, "{} != {}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn save_and_load_keep_weights() {
+        let eval: MlpEval = MlpEval::random(&[8, 4], Activation::ReLU, true, 32., 1);
+        let loaded: MlpEval = with_temp_file("mlp_round_trip", |path| {
+            eval.save(path).unwrap();
+            MlpEval::load(path).unwrap()
+        });
+        assert_eq!((loaded.use_mobility, loaded.output_scale), (eval.use_mobility, eval.output_scale));
+        assert_eq!(loaded.layers().len(), eval.layers().len());
+        for (a, b) in loaded.layers().iter().zip(eval.layers()) {
+            assert_eq!((&a.weight, &a.bias, a.activation), (&b.weight, &b.bias, b.activation));
+        }
+        assert_eq!(loaded.raw_eval(&State::init()), eval.raw_eval(&State::init()));
+    }
+
+    #[test]
+    fn load_rejects_huge_header() {
+        for (name, text) in [("mlp_layers", "mobility 0\nscale 1\nlayers 99999999999999\n"), ("mlp_size", "mobility 0\nscale 1\nlayers 1\nlayer 128 99999999999999 identity\n")] {
+            let loaded: io::Result<MlpEval> = with_temp_file(name, |path| {
+                fs::write(path, text).unwrap();
+                MlpEval::load(path)
+            });
+            assert_eq!(loaded.err().map(|error| error.kind()), Some(io::ErrorKind::InvalidData));
+        }
+    }
+}
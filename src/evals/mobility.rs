@@ -0,0 +1,40 @@
+use crate::components::{board::Board, bitboard::{BitBoard, BitBoardTrait}, constants::ScoreType, state::State};
+
+use super::EvalTrait;
+
+// 各項は(自分 - 相手)の差に重みを掛けて足し合わせる. 前線石は少ない方が良いので重みは負にする
+pub struct MobilityEval {
+    pub mobility_weight:    ScoreType,  // 着手可能数
+    pub potential_weight:   ScoreType,  // 相手の石に隣接する空きマス数
+    pub frontier_weight:    ScoreType,  // 空きマスに隣接する自分の石の数
+}
+
+impl MobilityEval {
+    pub fn new(mobility_weight: ScoreType, potential_weight: ScoreType, frontier_weight: ScoreType) -> MobilityEval {
+        MobilityEval {
+            mobility_weight,
+            potential_weight,
+            frontier_weight,
+        }
+    }
+}
+
+impl Default for MobilityEval {
+    fn default() -> MobilityEval {
+        MobilityEval::new(10, 3, -2)
+    }
+}
+
+impl EvalTrait for MobilityEval {
+    fn eval(&self, board: &Board) -> ScoreType {
+        let state: State = board.state;
+        let opponent_state: State = State::build(state.opponent_bit, state.player_bit);
+
+        let mobility: ScoreType = state.legal_actions_bitboard().count() - opponent_state.legal_actions_bitboard().count();
+        let potential: ScoreType = state.potential_actions_bitboard().count() - opponent_state.potential_actions_bitboard().count();
+        let frontier_bit: BitBoard = state.frontier_bitboard();
+        let frontier: ScoreType = (frontier_bit & state.player_bit).count() - (frontier_bit & state.opponent_bit).count();
+
+        self.mobility_weight * mobility + self.potential_weight * potential + self.frontier_weight * frontier
+    }
+}
@@ -0,0 +1,72 @@
+use crate::components::{board::Board, bitboard::{BitBoard, BitBoardTrait}, constants::ScoreType};
+
+use super::EvalTrait;
+
+// 空きマスが奇数なら手番側が最後の1手を打てる(パスがなければ)
+// 領域ごとにも同様で, 奇数領域が多いほど手番側が最後に打てる領域が多い
+pub struct ParityEval {
+    pub global_weight:  ScoreType,  // 空きマス全体の偶奇
+    pub region_weight:  ScoreType,  // 奇数領域の数
+}
+
+impl ParityEval {
+    pub fn new(global_weight: ScoreType, region_weight: ScoreType) -> ParityEval {
+        ParityEval {
+            global_weight,
+            region_weight,
+        }
+    }
+}
+
+impl Default for ParityEval {
+    fn default() -> ParityEval {
+        ParityEval::new(5, 2)
+    }
+}
+
+impl EvalTrait for ParityEval {
+    fn eval(&self, board: &Board) -> ScoreType {
+        let empty_bit: BitBoard = board.state.empty_bitboard();
+        if empty_bit == 0 {
+            return 0;
+        }
+        let global: ScoreType = if empty_bit.count() % 2 == 1 { 1 } else { -1 };
+        let odd_region_num: ScoreType = board.state.empty_regions().iter()
+            .filter(|region| region.count() % 2 == 1)
+            .count() as ScoreType;
+        self.global_weight * global + self.region_weight * odd_region_num
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::{constants::TOP_BIT, state::State};
+
+    const A1: BitBoard = TOP_BIT;
+    const H1: BitBoard = TOP_BIT >> 7;
+    const A8: BitBoard = TOP_BIT >> 56;
+    const B8: BitBoard = TOP_BIT >> 57;
+
+    fn board_with_empties(empty_bit: BitBoard) -> Board {
+        let mut board: Board = Board::init();
+        board.set_state(State::build(!empty_bit, 0));
+        board
+    }
+
+    #[test]
+    fn global_and_region_parity() {
+        let eval: ParityEval = ParityEval::new(5, 2);
+        // 空きマス3個(奇数)で, 奇数領域はa1の1つ
+        assert_eq!(eval.eval(&board_with_empties(A1 | A8 | B8)), 5 + 2);
+        // 空きマス4個(偶数)で, 奇数領域はa1とh1の2つ
+        assert_eq!(eval.eval(&board_with_empties(A1 | H1 | A8 | B8)), -5 + 2 * 2);
+        // 空きマス2個(偶数)で, 奇数領域はない
+        assert_eq!(eval.eval(&board_with_empties(A8 | B8)), -5);
+    }
+
+    #[test]
+    fn full_board_is_zero() {
+        assert_eq!(ParityEval::default().eval(&board_with_empties(0)), 0);
+    }
+}
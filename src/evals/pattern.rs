@@ -0,0 +1,359 @@
+use std::{fs, io, path::Path};
+
+use crate::components::{action::Action, board::Board, bitboard::{BitBoard, BitBoardTrait, SYMMETRY_NUM}, constants::{ScoreType, TOP_BIT, BOARD_LEN, BOARD_SIZE}, state::State};
+
+use super::{EvalTrait, weight_file::WeightTokens};
+
+pub type WeightType = f32;
+
+// (行, 列)で指定する. 各形は8通りの対称変換で盤面全体に展開される
+const PATTERN_SHAPES: [(&str, &[(usize, usize)]); 11] = [
+    ("edge2x", &[(0, 0), (0, 1), (0, 2), (0, 3), (0, 4), (0, 5), (0, 6), (0, 7), (1, 1), (1, 6)]),
+    ("corner2x5", &[(0, 0), (0, 1), (0, 2), (0, 3), (0, 4), (1, 0), (1, 1), (1, 2), (1, 3), (1, 4)]),
+    ("corner3x3", &[(0, 0), (0, 1), (0, 2), (1, 0), (1, 1), (1, 2), (2, 0), (2, 1), (2, 2)]),
+    ("diag8", &[(0, 0), (1, 1), (2, 2), (3, 3), (4, 4), (5, 5), (6, 6), (7, 7)]),
+    ("diag7", &[(0, 1), (1, 2), (2, 3), (3, 4), (4, 5), (5, 6), (6, 7)]),
+    ("diag6", &[(0, 2), (1, 3), (2, 4), (3, 5), (4, 6), (5, 7)]),
+    ("diag5", &[(0, 3), (1, 4), (2, 5), (3, 6), (4, 7)]),
+    ("diag4", &[(0, 4), (1, 5), (2, 6), (3, 7)]),
+    ("row2", &[(1, 0), (1, 1), (1, 2), (1, 3), (1, 4), (1, 5), (1, 6), (1, 7)]),
+    ("row3", &[(2, 0), (2, 1), (2, 2), (2, 3), (2, 4), (2, 5), (2, 6), (2, 7)]),
+    ("row4", &[(3, 0), (3, 1), (3, 2), (3, 3), (3, 4), (3, 5), (3, 6), (3, 7)]),
+];
+
+// 1つの形と, それを盤面に展開した各インスタンス
+// インデックスはマスを順に3進数で読んだもの(空き: 0, 手番側: 1, 相手: 2)
+pub struct Pattern {
+    pub name:   &'static str,
+    pub size:   usize,                  // マスの数
+    instances:  Vec<Vec<BitBoard>>,
+    fold_table: Vec<u32>,               // 形自身の対称変換で移り合うインデックスを最小のものにまとめる
+}
+
+impl Pattern {
+    fn new(name: &'static str, cells: &[(usize, usize)]) -> Pattern {
+        let squares: Vec<BitBoard> = cells.iter().map(|&(row, col)| TOP_BIT >> (BOARD_LEN * row + col)).collect();
+        let mut sorted_squares: Vec<BitBoard> = squares.clone();
+        sorted_squares.sort();
+
+        let mut instances: Vec<Vec<BitBoard>> = Vec::new();
+        let mut covered: Vec<Vec<BitBoard>> = Vec::new();
+        let mut permutations: Vec<Vec<usize>> = Vec::new();
+        for index in 0..SYMMETRY_NUM {
+            let mapped: Vec<BitBoard> = squares.iter().map(|square| square.symmetry(index)).collect();
+            let mut sorted_mapped: Vec<BitBoard> = mapped.clone();
+            sorted_mapped.sort();
+
+            // 元の形と同じマスの集合に移るなら, マスの並べ替えとして畳み込みに使う
+            if index != 0 && sorted_mapped == sorted_squares {
+                permutations.push(mapped.iter().map(|square| squares.iter().position(|s| s == square).unwrap()).collect());
+            }
+            if !covered.contains(&sorted_mapped) {
+                covered.push(sorted_mapped);
+                instances.push(mapped);
+            }
+        }
+
+        Pattern {
+            name,
+            size:   squares.len(),
+            instances,
+            fold_table: Pattern::build_fold_table(squares.len(), &permutations),
+        }
+    }
+
+    fn build_fold_table(size: usize, permutations: &[Vec<usize>]) -> Vec<u32> {
+        let index_num: usize = 3usize.pow(size as u32);
+        let mut digits: Vec<u32> = vec![0; size];
+        let mut permuted: Vec<u32> = vec![0; size];
+        (0..index_num as u32).map(|index| {
+            let mut rest: u32 = index;
+            for digit in digits.iter_mut().rev() {
+                *digit = rest % 3;
+                rest /= 3;
+            }
+            permutations.iter().fold(index, |folded, permutation| {
+                for (i, &p) in permutation.iter().enumerate() {
+                    permuted[p] = digits[i];
+                }
+                folded.min(permuted.iter().fold(0, |acc, &digit| acc * 3 + digit))
+            })
+        }).collect()
+    }
+
+    pub fn weight_num(&self) -> usize {
+        self.fold_table.len()
+    }
+
+    pub fn instance_num(&self) -> usize {
+        self.instances.len()
+    }
+
+    fn index(state: &State, squares: &[BitBoard]) -> usize {
+        squares.iter().fold(0, |acc, &square| {
+            let digit: usize = if state.player_bit & square != 0 {
+                1
+            } else if state.opponent_bit & square != 0 {
+                2
+            } else {
+                0
+            };
+            acc * 3 + digit
+        })
+    }
+}
+
+// 全パターンの全インスタンスを並べたときの, 畳み込み前のインデックス
+// [手番側から見たもの, 相手から見たもの]を持ち, 手番が変わるたびに入れ替える
+// 探索ではStateのmake_move/undo_moveと同じ引数でこちらも更新すれば, 評価のたびに数え直さずに済む
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatternIndexes {
+    indexes:    [Vec<u32>; 2],
+}
+
+impl PatternIndexes {
+    // 手番側から見ると, 置いたマスは空き(0)から1に, 裏返った石は2から1になる
+    pub fn make_move(&mut self, eval: &PatternEval, action: Action, flips: BitBoard) {
+        let [player, opponent] = &mut self.indexes;
+        eval.for_each_digit(action.bitboard, |instance, power| {
+            player[instance] += power;
+            opponent[instance] += 2 * power;
+        });
+        eval.for_each_digit(flips, |instance, power| {
+            player[instance] -= power;
+            opponent[instance] += power;
+        });
+        self.indexes.swap(0, 1);
+    }
+
+    pub fn undo_move(&mut self, eval: &PatternEval, action: Action, flips: BitBoard) {
+        self.indexes.swap(0, 1);
+        let [player, opponent] = &mut self.indexes;
+        eval.for_each_digit(action.bitboard, |instance, power| {
+            player[instance] -= power;
+            opponent[instance] -= 2 * power;
+        });
+        eval.for_each_digit(flips, |instance, power| {
+            player[instance] += power;
+            opponent[instance] -= power;
+        });
+    }
+
+    pub fn make_pass(&mut self) {
+        self.indexes.swap(0, 1);
+    }
+}
+
+// Logistello/Edax風のパターン評価. 評価値は各インスタンスの重みの和にbiasを足したもの
+// 重みは学習で作ったファイルから読み込む
+pub struct PatternEval {
+    pub patterns:   Vec<Pattern>,
+    pub weights:    Vec<Vec<WeightType>>,   // patternsと同じ順. 長さはそれぞれweight_num()
+    pub bias:   WeightType,
+    instance_patterns:  Vec<usize>,         // PatternIndexesの各インスタンスのパターン番号
+    square_digits:  Vec<Vec<(usize, u32)>>, // マスごとに, そのマスを含む(インスタンス番号, そのマスの桁の3の累乗)
+}
+
+impl PatternEval {
+    pub fn new() -> PatternEval {
+        let patterns: Vec<Pattern> = PATTERN_SHAPES.iter().map(|&(name, cells)| Pattern::new(name, cells)).collect();
+        let weights: Vec<Vec<WeightType>> = patterns.iter().map(|pattern| vec![0.; pattern.weight_num()]).collect();
+        let mut instance_patterns: Vec<usize> = Vec::new();
+        let mut square_digits: Vec<Vec<(usize, u32)>> = vec![Vec::new(); BOARD_SIZE];
+        for (pattern_id, pattern) in patterns.iter().enumerate() {
+            for squares in pattern.instances.iter() {
+                for (k, square) in squares.iter().enumerate() {
+                    square_digits[square.leading_zeros() as usize].push((instance_patterns.len(), 3u32.pow((pattern.size - 1 - k) as u32)));
+                }
+                instance_patterns.push(pattern_id);
+            }
+        }
+        PatternEval {
+            patterns,
+            weights,
+            bias:   0.,
+            instance_patterns,
+            square_digits,
+        }
+    }
+
+    // 形式: "bias <値>"の後に各パターンについて"<名前> <重みの数>"と重みを空白区切りで並べる. #から行末まではコメント
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<PatternEval> {
+        let mut tokens: WeightTokens = WeightTokens::read(path)?;
+        let mut eval: PatternEval = PatternEval::new();
+
+        tokens.expect("bias")?;
+        eval.bias = tokens.parse()?;
+        for (pattern, weights) in eval.patterns.iter().zip(eval.weights.iter_mut()) {
+            tokens.expect(pattern.name)?;
+            let weight_num: usize = tokens.parse()?;
+            if weight_num != pattern.weight_num() {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} needs {} weights, but {} was given", pattern.name, pattern.weight_num(), weight_num)));
+            }
+            for weight in weights.iter_mut() {
+                *weight = tokens.parse()?;
+            }
+        }
+        Ok(eval)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut text: String = format!("bias {}\n", self.bias);
+        for (pattern, weights) in self.patterns.iter().zip(self.weights.iter()) {
+            text += &format!("{} {}\n", pattern.name, weights.len());
+            let line: Vec<String> = weights.iter().map(|weight| weight.to_string()).collect();
+            text += &line.join(" ");
+            text += "\n";
+        }
+        fs::write(path, text)
+    }
+
+    // 探索の開始時などに一度だけ数える. 以降はPatternIndexesのmake_move/undo_moveで更新する
+    pub fn indexes(&self, state: &State) -> PatternIndexes {
+        let opponent_state: State = State::build(state.opponent_bit, state.player_bit);
+        let count = |state: &State| -> Vec<u32> {
+            self.patterns.iter()
+                .flat_map(|pattern| pattern.instances.iter().map(move |squares| Pattern::index(state, squares) as u32))
+                .collect()
+        };
+        PatternIndexes {
+            indexes:    [count(state), count(&opponent_state)],
+        }
+    }
+
+    // bitの各マスについて, そのマスを含むインスタンスと桁の3の累乗をfに渡す
+    fn for_each_digit(&self, bit: BitBoard, mut f: impl FnMut(usize, u32)) {
+        let mut rest: BitBoard = bit;
+        while rest != 0 {
+            let square: usize = rest.leading_zeros() as usize;
+            for &(instance, power) in self.square_digits[square].iter() {
+                f(instance, power);
+            }
+            rest &= !(TOP_BIT >> square);
+        }
+    }
+
+    // (パターン番号, 畳み込み済みインデックス)の列
+    fn folded_indexes<'a>(&'a self, indexes: &'a PatternIndexes) -> impl Iterator<Item = (usize, usize)> + 'a {
+        indexes.indexes[0].iter().zip(self.instance_patterns.iter())
+            .map(|(&index, &pattern_id)| (pattern_id, self.patterns[pattern_id].fold_table[index as usize] as usize))
+    }
+
+    // 学習用
+    pub fn features(&self, state: &State) -> Vec<(usize, usize)> {
+        self.folded_indexes(&self.indexes(state)).collect()
+    }
+
+    pub fn eval_indexes(&self, indexes: &PatternIndexes) -> WeightType {
+        self.folded_indexes(indexes)
+            .map(|(pattern_id, index)| self.weights[pattern_id][index])
+            .sum::<WeightType>() + self.bias
+    }
+
+    // 差分更新しない呼び出し元(EvalTrait::evalなど)向けに, その場でインデックスを数えて評価する
+    pub fn raw_eval(&self, state: &State) -> WeightType {
+        self.eval_indexes(&self.indexes(state))
+    }
+}
+
+impl Default for PatternEval {
+    fn default() -> PatternEval {
+        PatternEval::new()
+    }
+}
+
+impl EvalTrait for PatternEval {
+    fn eval(&self, board: &Board) -> ScoreType {
+        self.raw_eval(&board.state).round() as ScoreType
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::test_utils::{random_action, with_temp_file};
+
+    fn random_eval(seed: u64) -> PatternEval {
+        let mut rng: StdRng = StdRng::seed_from_u64(seed);
+        let mut eval: PatternEval = PatternEval::new();
+        for weight in eval.weights.iter_mut().flatten() {
+            *weight = rng.gen_range(-1.0..1.0);
+        }
+        eval.bias = 0.5;
+        eval
+    }
+
+    #[test]
+    fn raw_eval_is_symmetric() {
+        let eval: PatternEval = random_eval(0);
+        let mut rng: StdRng = StdRng::seed_from_u64(1);
+        let mut board: Board = Board::init();
+        while let Some(action) = random_action(&board.state, &mut rng) {
+            board = board.play_onestep(action);
+            let expected: WeightType = eval.raw_eval(&board.state);
+            for index in 0..SYMMETRY_NUM {
+                let actual: WeightType = eval.raw_eval(&board.state.symmetry(index));
+                assert!((actual - expected).abs() < 1e-3, "symmetry {}: {} != {}", index, actual, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn save_and_load_keep_weights() {
+        let eval: PatternEval = random_eval(2);
+        let loaded: PatternEval = with_temp_file("pattern", |path| {
+            eval.save(path).unwrap();
+            PatternEval::load(path).unwrap()
+        });
+        assert_eq!(loaded.bias, eval.bias);
+        assert_eq!(loaded.weights, eval.weights);
+    }
+
+    // make/undo/passで差分更新したインデックスは, 毎回数え直したものと一致し, undoで元に戻る
+    #[test]
+    fn incremental_indexes_match_full_count() {
+        let eval: PatternEval = random_eval(3);
+        let mut rng: StdRng = StdRng::seed_from_u64(4);
+        for _ in 0..10 {
+            let mut state: State = State::init();
+            let mut indexes: PatternIndexes = eval.indexes(&state);
+            // (打った手と裏返った石, パスならNone)
+            let mut history: Vec<Option<(Action, BitBoard)>> = Vec::new();
+            loop {
+                match random_action(&state, &mut rng) {
+                    Some(action) => {
+                        let flips: BitBoard = state.flips(action);
+                        state.make_move(action, flips);
+                        indexes.make_move(&eval, action, flips);
+                        history.push(Some((action, flips)));
+                    },
+                    None if history.last() == Some(&None) => break,
+                    None => {
+                        state.make_pass();
+                        indexes.make_pass();
+                        history.push(None);
+                    },
+                }
+                assert_eq!(indexes, eval.indexes(&state));
+                assert_eq!(eval.eval_indexes(&indexes), eval.raw_eval(&state));
+            }
+
+            for step in history.into_iter().rev() {
+                match step {
+                    Some((action, flips)) => {
+                        state.undo_move(action, flips);
+                        indexes.undo_move(&eval, action, flips);
+                    },
+                    None => {
+                        state.make_pass();
+                        indexes.make_pass();
+                    },
+                }
+                assert_eq!(indexes, eval.indexes(&state));
+            }
+            assert_eq!(state, State::init());
+        }
+    }
+}
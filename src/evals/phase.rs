@@ -0,0 +1,126 @@
+use crate::components::{board::Board, bitboard::BitBoardTrait, constants::ScoreType};
+
+use super::{EvalTrait, cell_score::CellEval, disc_count::DiscCountEval, mobility::MobilityEval, stability::StabilityEval};
+
+#[derive(Debug, Clone)]
+pub struct PhaseWeights {
+    pub disc_num:   i32,            // この重みを使う石数(両者の合計). 空きマス数は64 - disc_num
+    pub weights:    Vec<ScoreType>, // termsと同じ順に並べる
+}
+
+// 各項の評価値に盤面の石数に応じた重みを掛けて足し合わせる
+// 石数がphasesの間にあるときは前後の重みを線形補間する
+pub struct PhaseEval {
+    pub terms:  Vec<Box<dyn EvalTrait>>,
+    pub phases: Vec<PhaseWeights>,  // disc_numの昇順
+}
+
+impl PhaseEval {
+    pub fn new(terms: Vec<Box<dyn EvalTrait>>, mut phases: Vec<PhaseWeights>) -> PhaseEval {
+        assert!(!phases.is_empty(), "PhaseEval needs at least one phase");
+        assert!(phases.iter().all(|phase| phase.weights.len() == terms.len()), "each phase needs one weight per term");
+        phases.sort_by_key(|phase| phase.disc_num);
+        PhaseEval {
+            terms,
+            phases,
+        }
+    }
+
+    // disc_numでの各項の重み. 補間の端数を抑えるため(分子, 分母)で返す
+    fn weights(&self, disc_num: i32) -> (Vec<ScoreType>, ScoreType) {
+        let next_index: usize = self.phases.partition_point(|phase| phase.disc_num < disc_num);
+        if next_index == 0 {
+            return (self.phases[0].weights.clone(), 1);
+        }
+        if next_index == self.phases.len() {
+            return (self.phases[next_index - 1].weights.clone(), 1);
+        }
+
+        let prev: &PhaseWeights = &self.phases[next_index - 1];
+        let next: &PhaseWeights = &self.phases[next_index];
+        let weights: Vec<ScoreType> = prev.weights.iter().zip(next.weights.iter())
+            .map(|(prev_weight, next_weight)| prev_weight * (next.disc_num - disc_num) + next_weight * (disc_num - prev.disc_num))
+            .collect();
+        (weights, next.disc_num - prev.disc_num)
+    }
+}
+
+// 序盤は石を少なく持って着手可能数を稼ぎ, 終盤ほど確定石と石数を重く見る
+impl Default for PhaseEval {
+    fn default() -> PhaseEval {
+        PhaseEval::new(
+            vec![
+                Box::new(CellEval::default()),
+                Box::new(MobilityEval::default()),
+                Box::new(StabilityEval::new(1)),
+                Box::new(DiscCountEval {}),
+            ],
+            vec![
+                PhaseWeights { disc_num: 4, weights: vec![3, 2, 10, -1] },
+                PhaseWeights { disc_num: 32, weights: vec![2, 2, 20, 0] },
+                PhaseWeights { disc_num: 50, weights: vec![1, 1, 20, 2] },
+                PhaseWeights { disc_num: 64, weights: vec![0, 0, 10, 20] },
+            ],
+        )
+    }
+}
+
+impl EvalTrait for PhaseEval {
+    fn eval(&self, board: &Board) -> ScoreType {
+        let disc_num: i32 = (board.state.player_bit | board.state.opponent_bit).count();
+        let (weights, denominator) = self.weights(disc_num);
+        let score: ScoreType = self.terms.iter().zip(weights.iter())
+            .map(|(term, weight)| weight * term.eval(board))
+            .sum();
+        score / denominator
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 順番を入れ替えて渡してもdisc_numの昇順に並べ直される
+    fn sample_eval() -> PhaseEval {
+        PhaseEval::new(
+            vec![Box::new(DiscCountEval {}), Box::new(DiscCountEval {})],
+            vec![
+                PhaseWeights { disc_num: 30, weights: vec![0, 20] },
+                PhaseWeights { disc_num: 10, weights: vec![10, 0] },
+                PhaseWeights { disc_num: 40, weights: vec![4, 4] },
+            ],
+        )
+    }
+
+    // (分子, 分母)がexpectedに等しい
+    fn assert_weights(eval: &PhaseEval, disc_num: i32, expected: &[ScoreType]) {
+        let (weights, denominator) = eval.weights(disc_num);
+        assert!(denominator > 0);
+        let expected: Vec<ScoreType> = expected.iter().map(|weight| weight * denominator).collect();
+        assert_eq!(weights, expected, "disc_num {}", disc_num);
+    }
+
+    #[test]
+    fn weights_outside_phases_are_clamped() {
+        let eval: PhaseEval = sample_eval();
+        assert_weights(&eval, 4, &[10, 0]);
+        assert_weights(&eval, 64, &[4, 4]);
+    }
+
+    #[test]
+    fn weights_on_phase_boundaries() {
+        let eval: PhaseEval = sample_eval();
+        assert_weights(&eval, 10, &[10, 0]);
+        assert_weights(&eval, 30, &[0, 20]);
+        assert_weights(&eval, 40, &[4, 4]);
+    }
+
+    #[test]
+    fn weights_are_interpolated_between_phases() {
+        let eval: PhaseEval = sample_eval();
+        assert_weights(&eval, 20, &[5, 10]);
+        assert_weights(&eval, 35, &[2, 12]);
+        // 端数は分母で割るまで残す
+        assert_eq!(eval.weights(11), (vec![10 * 19, 20], 20));
+    }
+}
@@ -0,0 +1,29 @@
+use crate::components::{board::Board, bitboard::{BitBoard, BitBoardTrait}, constants::ScoreType};
+
+use super::EvalTrait;
+
+// 確定石の数の差(自分 - 相手)に重みを掛ける
+pub struct StabilityEval {
+    pub weight: ScoreType,
+}
+
+impl StabilityEval {
+    pub fn new(weight: ScoreType) -> StabilityEval {
+        StabilityEval {
+            weight,
+        }
+    }
+}
+
+impl Default for StabilityEval {
+    fn default() -> StabilityEval {
+        StabilityEval::new(20)
+    }
+}
+
+impl EvalTrait for StabilityEval {
+    fn eval(&self, board: &Board) -> ScoreType {
+        let stable_bit: BitBoard = board.stable_bitboard();
+        self.weight * ((stable_bit & board.state.player_bit).count() - (stable_bit & board.state.opponent_bit).count())
+    }
+}
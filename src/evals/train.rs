@@ -0,0 +1,417 @@
+use std::{fs, io, path::Path};
+
+use rand::{SeedableRng, rngs::StdRng, seq::SliceRandom};
+
+use crate::{components::{bitboard::BitBoard, constants::{ScoreType, TOP_BIT, BOARD_SIZE}, state::State}, math::{functions::Activation, matrix::{Matrix, NumType, Vector}, optimizer::{LearningRate, Optimizer}, variable::{self, Graph, Variable}}};
+
+use super::{cell_score::CellEval, mlp::{DenseLayer, MlpEval}, pattern::PatternEval};
+
+// scoreは手番側(state.player_bit)から見た目標値. 最終石差など
+#[derive(Debug, Clone, Copy)]
+pub struct Sample {
+    pub state:  State,
+    pub score:  NumType,
+}
+
+// 1行に"<手番側の石(16進)> <相手の石(16進)> <目標値>". #から行末まではコメント
+pub fn load_samples<P: AsRef<Path>>(path: P) -> io::Result<Vec<Sample>> {
+    let text: String = fs::read_to_string(path)?;
+    let invalid = |line: &str| io::Error::new(io::ErrorKind::InvalidData, format!("invalid sample: {}", line));
+    let mut samples: Vec<Sample> = Vec::new();
+    for line in text.lines().map(|line| line.split('#').next().unwrap_or("").trim()) {
+        if line.is_empty() {
+            continue;
+        }
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens.len() != 3 {
+            return Err(invalid(line));
+        }
+        let player_bit: BitBoard = BitBoard::from_str_radix(tokens[0], 16).map_err(|_| invalid(line))?;
+        let opponent_bit: BitBoard = BitBoard::from_str_radix(tokens[1], 16).map_err(|_| invalid(line))?;
+        let score: NumType = tokens[2].parse().map_err(|_| invalid(line))?;
+        samples.push(Sample {
+            state:  State::build(player_bit, opponent_bit),
+            score,
+        });
+    }
+    Ok(samples)
+}
+
+// 学習できる評価関数. 損失は二乗誤差のバッチ平均
+pub trait Trainable {
+    fn params_mut(&mut self) -> &mut [Matrix];
+    fn predict(&self, state: &State) -> NumType;
+    // (損失, paramsと同じ順の勾配)
+    fn loss_and_grads(&self, batch: &[Sample]) -> (NumType, Vec<Matrix>);
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TrainConfig {
+    pub epochs: usize,
+    pub batch_size: usize,
+    pub learning_rate:  LearningRate,
+    pub validation_ratio:   f64,    // samplesのうち検証に回す割合. [0, 1)
+    pub seed:   u64,
+    pub verbose:    bool,           // epochごとに損失を表示する
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct EpochReport {
+    pub epoch:  usize,
+    pub learning_rate:  NumType,
+    pub train_loss: NumType,
+    pub validation_loss:    Option<NumType>,    // 検証データがなければNone
+}
+
+pub fn mean_loss<M: Trainable>(model: &M, samples: &[Sample]) -> NumType {
+    let loss: NumType = samples.iter().map(|sample| (model.predict(&sample.state) - sample.score).powi(2)).sum();
+    loss / samples.len().max(1) as NumType
+}
+
+// シャッフルして(学習用, 検証用)に分ける
+fn split_validation(samples: &[Sample], validation_ratio: f64, rng: &mut StdRng) -> (Vec<Sample>, Vec<Sample>) {
+    assert!(
+        (0.0..1.0).contains(&validation_ratio),
+        "validation_ratio must be in [0, 1), but {} was given", validation_ratio
+    );
+    let mut shuffled: Vec<Sample> = samples.to_vec();
+    shuffled.shuffle(rng);
+    let validation_num: usize = (shuffled.len() as f64 * validation_ratio) as usize;
+    let validation: Vec<Sample> = shuffled.split_off(shuffled.len() - validation_num);
+    (shuffled, validation)
+}
+
+pub fn train<M: Trainable>(model: &mut M, samples: &[Sample], optimizer: &mut dyn Optimizer, config: &TrainConfig) -> Vec<EpochReport> {
+    let mut rng: StdRng = StdRng::seed_from_u64(config.seed);
+    let (mut train_samples, validation) = split_validation(samples, config.validation_ratio, &mut rng);
+
+    let mut reports: Vec<EpochReport> = Vec::with_capacity(config.epochs);
+    for epoch in 0..config.epochs {
+        let learning_rate: NumType = config.learning_rate.rate(epoch);
+        train_samples.shuffle(&mut rng);
+
+        let mut loss_sum: NumType = 0.;
+        for batch in train_samples.chunks(config.batch_size.max(1)) {
+            let (loss, grads) = model.loss_and_grads(batch);
+            optimizer.step(model.params_mut(), &grads, learning_rate);
+            loss_sum += loss * batch.len() as NumType;
+        }
+
+        let report: EpochReport = EpochReport {
+            epoch,
+            learning_rate,
+            train_loss: loss_sum / train_samples.len().max(1) as NumType,
+            validation_loss:    if validation.is_empty() { None } else { Some(mean_loss(model, &validation)) },
+        };
+        if config.verbose {
+            match report.validation_loss {
+                Some(validation_loss) => println!("epoch {}\tlr {}\ttrain {}\tvalidation {}", epoch, learning_rate, report.train_loss, validation_loss),
+                None => println!("epoch {}\tlr {}\ttrain {}", epoch, learning_rate, report.train_loss),
+            }
+        }
+        reports.push(report);
+    }
+    reports
+}
+
+// CellEvalのマスごとの重み. 特徴量は手番側の石で1, 相手の石で-1
+pub struct CellModel {
+    params: [Matrix; 2],    // 重み(1x64), バイアス(1x1)
+}
+
+impl CellModel {
+    pub fn new() -> CellModel {
+        CellModel {
+            params: [Matrix::zeros(1, BOARD_SIZE), Matrix::zeros(1, 1)],
+        }
+    }
+
+    fn features(state: &State) -> [NumType; BOARD_SIZE] {
+        let mut features: [NumType; BOARD_SIZE] = [0.; BOARD_SIZE];
+        let mut mask: BitBoard = TOP_BIT;
+        for feature in features.iter_mut() {
+            if state.player_bit & mask != 0 {
+                *feature = 1.;
+            } else if state.opponent_bit & mask != 0 {
+                *feature = -1.;
+            }
+            mask >>= 1;
+        }
+        features
+    }
+
+    // CellEvalは整数の重みなのでscale倍して丸める
+    pub fn to_eval(&self, scale: NumType) -> CellEval {
+        let mut score_table: [ScoreType; BOARD_SIZE] = [0; BOARD_SIZE];
+        for (score, weight) in score_table.iter_mut().zip(self.params[0].as_slice()) {
+            *score = (weight * scale).round() as ScoreType;
+        }
+        CellEval::new(score_table)
+    }
+}
+
+impl Default for CellModel {
+    fn default() -> CellModel {
+        CellModel::new()
+    }
+}
+
+impl Trainable for CellModel {
+    fn params_mut(&mut self) -> &mut [Matrix] {
+        &mut self.params
+    }
+
+    fn predict(&self, state: &State) -> NumType {
+        let features: [NumType; BOARD_SIZE] = CellModel::features(state);
+        let weights: &[NumType] = self.params[0].as_slice();
+        features.iter().zip(weights).map(|(x, w)| x * w).sum::<NumType>() + self.params[1].get(0, 0)
+    }
+
+    fn loss_and_grads(&self, batch: &[Sample]) -> (NumType, Vec<Matrix>) {
+        let mut grads: Vec<Matrix> = vec![Matrix::zeros(1, BOARD_SIZE), Matrix::zeros(1, 1)];
+        let mut loss: NumType = 0.;
+        let scale: NumType = 2. / batch.len() as NumType;
+        for sample in batch {
+            let error: NumType = self.predict(&sample.state) - sample.score;
+            loss += error * error;
+            for (grad, x) in grads[0].as_mut_slice().iter_mut().zip(CellModel::features(&sample.state)) {
+                *grad += scale * error * x;
+            }
+            grads[1].as_mut_slice()[0] += scale * error;
+        }
+        (loss / batch.len() as NumType, grads)
+    }
+}
+
+// PatternEvalのテーブル. 各テーブルは(1 x weight_num), 最後がバイアス(1x1)
+pub struct PatternModel {
+    eval:   PatternEval,
+    params: Vec<Matrix>,
+}
+
+impl PatternModel {
+    pub fn new(eval: PatternEval) -> PatternModel {
+        let mut params: Vec<Matrix> = eval.weights.iter().map(|weights| Matrix::from_flat(1, weights.len(), weights.clone())).collect();
+        params.push(Matrix::new(vec![vec![eval.bias]]));
+        PatternModel {
+            eval,
+            params,
+        }
+    }
+
+    pub fn into_eval(mut self) -> PatternEval {
+        for (weights, param) in self.eval.weights.iter_mut().zip(self.params.iter()) {
+            weights.copy_from_slice(param.as_slice());
+        }
+        self.eval.bias = self.params[self.params.len() - 1].get(0, 0);
+        self.eval
+    }
+}
+
+impl Trainable for PatternModel {
+    fn params_mut(&mut self) -> &mut [Matrix] {
+        &mut self.params
+    }
+
+    fn predict(&self, state: &State) -> NumType {
+        let bias: NumType = self.params[self.params.len() - 1].get(0, 0);
+        self.eval.features(state).into_iter()
+            .map(|(pattern_id, index)| self.params[pattern_id].get(0, index))
+            .sum::<NumType>() + bias
+    }
+
+    // 勾配はほとんど0だが, Optimizerに渡すためにテーブルと同じ大きさで作る
+    fn loss_and_grads(&self, batch: &[Sample]) -> (NumType, Vec<Matrix>) {
+        let mut grads: Vec<Matrix> = self.params.iter().map(|param| Matrix::zeros(param.rows(), param.cols())).collect();
+        let bias_index: usize = grads.len() - 1;
+        let mut loss: NumType = 0.;
+        let scale: NumType = 2. / batch.len() as NumType;
+        for sample in batch {
+            let error: NumType = self.predict(&sample.state) - sample.score;
+            loss += error * error;
+            for (pattern_id, index) in self.eval.features(&sample.state) {
+                grads[pattern_id].as_mut_slice()[index] += scale * error;
+            }
+            grads[bias_index].as_mut_slice()[0] += scale * error;
+        }
+        (loss / batch.len() as NumType, grads)
+    }
+}
+
+// MlpEvalの各層の重みとバイアス. バイアスは列ベクトル(rows x 1)で持つ
+pub struct MlpModel {
+    eval:   MlpEval,
+    params: Vec<Matrix>,
+}
+
+impl MlpModel {
+    pub fn new(eval: MlpEval) -> MlpModel {
+        let params: Vec<Matrix> = eval.layers().iter()
+            .flat_map(|layer| [layer.weight.clone(), layer.bias.clone().vec2mat()])
+            .collect();
+        MlpModel {
+            eval,
+            params,
+        }
+    }
+
+    fn activations(&self) -> Vec<Activation> {
+        self.eval.layers().iter().map(|layer| layer.activation).collect()
+    }
+
+    pub fn into_eval(self) -> MlpEval {
+        let activations: Vec<Activation> = self.activations();
+        let layers: Vec<DenseLayer> = self.params.chunks(2).zip(activations).map(|(param, activation)| DenseLayer {
+            weight: param[0].clone(),
+            bias:   param[1].clone().mat2vec(),
+            activation,
+        }).collect();
+        MlpEval::new(layers, self.eval.use_mobility, self.eval.output_scale)
+    }
+}
+
+impl Trainable for MlpModel {
+    fn params_mut(&mut self) -> &mut [Matrix] {
+        &mut self.params
+    }
+
+    fn predict(&self, state: &State) -> NumType {
+        let activations: Vec<Activation> = self.activations();
+        let output: Vector = self.params.chunks(2).zip(activations).fold(self.eval.features(state), |x, (param, activation)| {
+            (&param[0].mul_vec(&x) + &param[1].clone().mat2vec()).map(|v| activation.forward(v))
+        });
+        output.data[0]
+    }
+
+    // バッチを列に並べて計算グラフで逆伝播する
+    fn loss_and_grads(&self, batch: &[Sample]) -> (NumType, Vec<Matrix>) {
+        let feature_num: usize = MlpEval::feature_num(self.eval.use_mobility);
+        let mut inputs: Matrix = Matrix::zeros(feature_num, batch.len());
+        for (col, sample) in batch.iter().enumerate() {
+            for (row, &x) in self.eval.features(&sample.state).data.iter().enumerate() {
+                inputs.set(row, col, x);
+            }
+        }
+        let targets: Matrix = Matrix::from_flat(1, batch.len(), batch.iter().map(|sample| sample.score).collect());
+
+        let graph: Graph = Graph::new();
+        let params: Vec<Variable> = self.params.iter().map(|param| graph.variable(param.clone())).collect();
+        let output: Variable = params.chunks(2).zip(self.activations()).fold(graph.variable(inputs), |x, (param, activation)| {
+            param[0].matmul(x).add_broadcast(param[1]).apply_activation(activation)
+        });
+        let loss: Variable = variable::mse(graph.variable(targets), output) * (1. / batch.len() as NumType);
+        loss.backward();
+        (loss.value().get(0, 0), params.iter().map(|param| param.grad()).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+    use crate::{math::{optimizer::Sgd, variable::check_grad_at}, test_utils::random_board};
+
+    // ランダムに打った局面と, 石差から作った目標値
+    fn random_samples(num: usize, seed: u64) -> Vec<Sample> {
+        let mut rng: StdRng = StdRng::seed_from_u64(seed);
+        (0..num).map(|_| {
+            let move_num: usize = rng.gen_range(1..=40);
+            let state: State = random_board(move_num, &mut rng).state;
+            Sample {
+                state,
+                score:  (state.player_bit.count_ones() as NumType - state.opponent_bit.count_ones() as NumType) / 8.,
+            }
+        }).collect()
+    }
+
+    fn config(validation_ratio: f64) -> TrainConfig {
+        TrainConfig {
+            epochs: 20,
+            batch_size: 8,
+            learning_rate:  LearningRate::Constant(0.01),
+            validation_ratio,
+            seed:   0,
+            verbose:    false,
+        }
+    }
+
+    // モデルの各パラメータについて, loss_and_gradsの勾配と数値微分を比べる. entriesは(パラメータ番号, 列)
+    fn check_model_grads<M: Trainable>(model: &mut M, batch: &[Sample], entries: &[(usize, usize)]) {
+        let (_, grads) = model.loss_and_grads(batch);
+        for &(param_id, col) in entries {
+            for row in 0..grads[param_id].rows() {
+                let value: NumType = model.params_mut()[param_id].get(row, col);
+                check_grad_at(|shifted| {
+                    model.params_mut()[param_id].set(row, col, shifted);
+                    model.loss_and_grads(batch).0
+                }, value, grads[param_id].get(row, col));
+                model.params_mut()[param_id].set(row, col, value);
+            }
+        }
+    }
+
+    #[test]
+    fn split_keeps_all_samples() {
+        let samples: Vec<Sample> = random_samples(50, 0);
+        let mut rng: StdRng = StdRng::seed_from_u64(0);
+        let (train_samples, validation) = split_validation(&samples, 0.2, &mut rng);
+        assert_eq!((train_samples.len(), validation.len()), (40, 10));
+        let key = |sample: &Sample| (sample.state.player_bit, sample.state.opponent_bit, sample.score.to_bits());
+        let mut expected: Vec<_> = samples.iter().map(key).collect();
+        let mut actual: Vec<_> = train_samples.iter().chain(&validation).map(key).collect();
+        expected.sort();
+        actual.sort();
+        assert_eq!(actual, expected);
+
+        let (train_samples, validation) = split_validation(&samples, 0., &mut rng);
+        assert_eq!((train_samples.len(), validation.len()), (50, 0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn train_rejects_invalid_validation_ratio() {
+        train(&mut CellModel::new(), &random_samples(10, 0), &mut Sgd, &config(1.5));
+    }
+
+    #[test]
+    fn train_lowers_cell_model_loss() {
+        let samples: Vec<Sample> = random_samples(200, 1);
+        let mut model: CellModel = CellModel::new();
+        let initial_loss: NumType = mean_loss(&model, &samples);
+        let reports: Vec<EpochReport> = train(&mut model, &samples, &mut Sgd, &config(0.1));
+        assert_eq!(reports.len(), 20);
+        assert!(reports[0].validation_loss.is_some());
+        assert!(reports[19].train_loss < reports[0].train_loss);
+        assert!(mean_loss(&model, &samples) < 0.5 * initial_loss);
+    }
+
+    #[test]
+    fn mlp_model_grads_match_numerical() {
+        let batch: Vec<Sample> = random_samples(4, 2);
+        let mut model: MlpModel = MlpModel::new(MlpEval::random(&[4], Activation::Tanh, true, 1., 0));
+        // 1層目の重みは値が0でない特徴量の列だけ, 2層目以降は全て
+        let mut entries: Vec<(usize, usize)> = model.eval.features(&batch[0].state).data.iter().enumerate()
+            .filter(|&(_, &x)| x != 0.)
+            .map(|(col, _)| (0, col))
+            .collect();
+        entries.extend([(1, 0), (2, 0), (2, 1), (2, 2), (2, 3), (3, 0)]);
+        check_model_grads(&mut model, &batch, &entries);
+    }
+
+    #[test]
+    fn pattern_model_grads_match_numerical() {
+        let batch: Vec<Sample> = random_samples(4, 3);
+        let mut eval: PatternEval = PatternEval::new();
+        let mut rng: StdRng = StdRng::seed_from_u64(0);
+        for weight in eval.weights.iter_mut().flatten() {
+            *weight = rng.gen_range(-0.1..0.1);
+        }
+        let mut model: PatternModel = PatternModel::new(eval);
+        // バッチに現れるインデックスと, 現れないインデックス0, バイアス
+        let bias_id: usize = model.params_mut().len() - 1;
+        let mut entries: Vec<(usize, usize)> = model.eval.features(&batch[0].state);
+        entries.extend([(0, 0), (bias_id, 0)]);
+        check_model_grads(&mut model, &batch, &entries);
+    }
+}
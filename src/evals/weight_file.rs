@@ -0,0 +1,32 @@
+use std::{fs, io, path::Path, str::FromStr, vec::IntoIter};
+
+// 重みファイルを空白区切りで読む. #から行末まではコメント
+pub(super) struct WeightTokens {
+    tokens: IntoIter<String>,
+}
+
+impl WeightTokens {
+    pub fn read<P: AsRef<Path>>(path: P) -> io::Result<WeightTokens> {
+        let text: String = fs::read_to_string(path)?;
+        let tokens: Vec<String> = text.lines()
+            .map(|line| line.split('#').next().unwrap_or(""))
+            .flat_map(|line| line.split_whitespace())
+            .map(|token| token.to_string())
+            .collect();
+        Ok(WeightTokens {
+            tokens: tokens.into_iter(),
+        })
+    }
+
+    pub fn expect(&mut self, expected: &str) -> io::Result<()> {
+        match self.tokens.next() {
+            Some(token) if token == expected => Ok(()),
+            token => Err(io::Error::new(io::ErrorKind::InvalidData, format!("expected {}, but {:?} was given", expected, token))),
+        }
+    }
+
+    pub fn parse<T: FromStr>(&mut self) -> io::Result<T> {
+        let token: String = self.tokens.next().ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "weight file ended early"))?;
+        token.parse().map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("invalid value: {}", token)))
+    }
+}
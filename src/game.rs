@@ -0,0 +1,48 @@
+use crate::{agents::Agent, components::{board::Board, action::Action, enums::{Turn, BoardStatus}}};
+
+pub struct Game {
+    black_agent:    Box<dyn Agent>,
+    white_agent:    Box<dyn Agent>,
+}
+
+impl Game {
+    pub fn new(black_agent: Box<dyn Agent>, white_agent: Box<dyn Agent>) -> Game {
+        Game {
+            black_agent,
+            white_agent,
+        }
+    }
+
+    fn agent(&self, turn: Turn) -> &dyn Agent {
+        if turn == Turn::Black {
+            self.black_agent.as_ref()
+        } else {
+            self.white_agent.as_ref()
+        }
+    }
+
+    pub fn playout(&self, print_flag: bool) -> (i32, i32, Turn) {
+        self.playout_from(Board::init(), print_flag)
+    }
+
+    pub fn playout_from(&self, board: Board, print_flag: bool) -> (i32, i32, Turn) {
+        let mut tmp: Board = board;
+        let mut tmp_status: BoardStatus = tmp.status();
+        while tmp_status != BoardStatus::Finished {
+            if tmp_status == BoardStatus::Pass {
+                tmp = tmp.play_pass();
+            } else {
+                let action: Action = self.agent(tmp.turn()).next_action(&tmp);
+                tmp = tmp.play_onestep(action);
+            }
+            tmp_status = tmp.status();
+        }
+        let result = tmp.result();
+
+        if print_flag {
+            tmp.print();
+            println!("Result:{}", result.2);
+        }
+        result
+    }
+}
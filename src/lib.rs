@@ -0,0 +1,8 @@
+pub mod components;
+pub mod agents;
+pub mod evals;
+pub mod game;
+pub mod math;
+
+#[cfg(test)]
+mod test_utils;
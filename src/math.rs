@@ -1,2 +1,4 @@
 pub mod matrix;
-pub mod functions;
\ No newline at end of file
+pub mod functions;
+pub mod variable;
+pub mod optimizer;
\ No newline at end of file
@@ -2,8 +2,10 @@ use std::f32::consts::E;
 
 use super::{matrix::NumType, matrix::Vector};
 
-trait Function {
+// derivativeは逆伝播用. outputにはforward(input)の値を渡す
+pub trait Function {
     fn forward(input: NumType) -> NumType;
+    fn derivative(input: NumType, output: NumType) -> NumType;
     fn forward_vec(input: &Vector) -> Vector {
         Vector {
             rows: input.rows,
@@ -14,38 +16,103 @@ trait Function {
     }
 }
 
-struct Sin;
+pub struct Sin;
 impl Function for Sin {
     fn forward(input: NumType) -> NumType {
         f32::sin(input)
     }
+
+    fn derivative(input: NumType, _output: NumType) -> NumType {
+        f32::cos(input)
+    }
 }
 
-struct Cos;
+pub struct Cos;
 impl Function for Cos {
     fn forward(input: NumType) -> NumType {
         f32::cos(input)
     }
+
+    fn derivative(input: NumType, _output: NumType) -> NumType {
+        -f32::sin(input)
+    }
 }
 
-struct ReLU;
+pub struct ReLU;
 impl Function for ReLU {
     fn forward(input: NumType) -> NumType {
         input.max(NumType::default())
     }
+
+    fn derivative(input: NumType, _output: NumType) -> NumType {
+        if input > 0. { 1. } else { 0. }
+    }
 }
 
-struct Tanh;
+pub struct Tanh;
 impl Function for Tanh {
     fn forward(input: NumType) -> NumType {
         input.tanh()
     }
+
+    fn derivative(_input: NumType, output: NumType) -> NumType {
+        1. - output * output
+    }
 }
 
-struct Sigmoid;
+pub struct Sigmoid;
 impl Function for Sigmoid {
     fn forward(input: NumType) -> NumType {
-        1.0 / (1.0 + E.powf(input))
+        1.0 / (1.0 + E.powf(-input))
+    }
+
+    fn derivative(_input: NumType, output: NumType) -> NumType {
+        output * (1. - output)
+    }
+}
+
+pub struct Identity;
+impl Function for Identity {
+    fn forward(input: NumType) -> NumType {
+        input
+    }
+
+    fn derivative(_input: NumType, _output: NumType) -> NumType {
+        1.
+    }
+}
+
+// ファイルから読み込む層などで実行時に活性化関数を選ぶための列挙
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Activation {
+    Identity,
+    ReLU,
+    Tanh,
+    Sigmoid,
+}
+
+impl Activation {
+    pub fn forward(&self, input: NumType) -> NumType {
+        match self {
+            Activation::Identity => Identity::forward(input),
+            Activation::ReLU => ReLU::forward(input),
+            Activation::Tanh => Tanh::forward(input),
+            Activation::Sigmoid => Sigmoid::forward(input),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Activation::Identity => "identity",
+            Activation::ReLU => "relu",
+            Activation::Tanh => "tanh",
+            Activation::Sigmoid => "sigmoid",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Activation> {
+        [Activation::Identity, Activation::ReLU, Activation::Tanh, Activation::Sigmoid].into_iter()
+            .find(|activation| activation.name() == name)
     }
 }
 
@@ -55,4 +122,23 @@ pub fn mse(target: &Vector, x: &Vector) -> NumType {
         "Vector length does not match in mse()"
     );
     x.data.iter().zip(&target.data).map(|(v1, v2)| (v1 - v2).powi(2)).sum()
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sigmoid() {
+        assert_eq!(Sigmoid::forward(0.), 0.5);
+        assert!(Sigmoid::forward(10.) > 0.99);
+        assert!(Sigmoid::forward(-10.) < 0.01);
+    }
+
+    #[test]
+    fn forward_vec_and_mse() {
+        let x: Vector = ReLU::forward_vec(&Vector::new(vec![-1., 2.]));
+        assert_eq!(x.data, vec![0., 2.]);
+        assert_eq!(mse(&Vector::new(vec![1., 1.]), &x), 2.);
+    }
+}
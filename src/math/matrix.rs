@@ -3,6 +3,7 @@ use std::fmt;
 
 pub type NumType = f32;
 
+#[derive(Clone, Debug, PartialEq)]
 pub struct Vector {
     pub rows:   usize,
     pub data:   Vec<NumType>,
@@ -12,7 +13,7 @@ impl Vector {
     pub fn new(data: Vec<NumType>) -> Self {
         Vector {
             rows: data.len(),
-            data: data,
+            data,
         }
     }
 
@@ -28,68 +29,63 @@ impl Vector {
         Matrix {
             rows: self.rows,
             cols: 1,
-            data: self.data
-                .chunks(1)
-                .map(|chunk| chunk.to_vec())
-                .collect(),
+            data: self.data,
         }
     }
-}
-
-impl ops::Add for &Vector {
-    type Output = Vector;
 
-    fn add(self, other: &Vector) -> Vector {
+    pub fn dot(&self, other: &Vector) -> NumType {
         assert_eq!(
             self.rows, other.rows,
-            "Vector dimensions do not match for addition (rows)"
+            "Vector dimensions do not match for dot product (rows)"
         );
+        self.data.iter().zip(&other.data).map(|(a, b)| a * b).sum()
+    }
 
-        let mut data = Vector::zeros(self.rows).data;
-        for i in 0..self.rows {
-            data[i] = self.data[i] + other.data[i];
-        }
-
+    pub fn map<F: Fn(NumType) -> NumType>(&self, f: F) -> Self {
         Vector {
-            data: self.data.iter().zip(&other.data)
-                .map(|(a, b)| a + b).collect(),
+            data: self.data.iter().map(|&element| f(element)).collect(),
             ..*self
         }
     }
-}
 
-impl ops::Sub for &Vector {
-    type Output = Vector;
+    // 要素ごとの積
+    pub fn hadamard(&self, other: &Vector) -> Self {
+        self.zip_with(other, |a, b| a * b)
+    }
 
-    fn sub(self, other: &Vector) -> Vector {
+    fn zip_with<F: Fn(NumType, NumType) -> NumType>(&self, other: &Vector, f: F) -> Self {
         assert_eq!(
             self.rows, other.rows,
-            "Vector dimensions do not match for subtraction (rows)"
+            "Vector dimensions do not match (rows)"
         );
-
-        let mut data = Vector::zeros(self.rows).data;
-        for i in 0..self.rows {
-            data[i] = self.data[i] + other.data[i];
-        }
-
         Vector {
             data: self.data.iter().zip(&other.data)
-                .map(|(a, b)| a + b).collect(),
+                .map(|(&a, &b)| f(a, b)).collect(),
             ..*self
         }
     }
 }
 
+impl ops::Add for &Vector {
+    type Output = Vector;
+
+    fn add(self, other: &Vector) -> Vector {
+        self.zip_with(other, |a, b| a + b)
+    }
+}
+
+impl ops::Sub for &Vector {
+    type Output = Vector;
+
+    fn sub(self, other: &Vector) -> Vector {
+        self.zip_with(other, |a, b| a - b)
+    }
+}
+
 impl ops::Mul<&Vector> for NumType {
     type Output = Vector;
     fn mul(self, other: &Vector) -> Vector {
-        let data = other.data.iter()
-        .map(|&element| element * self).collect();
-
-        Vector {
-            data,
-            ..*other
-        }
+        other.map(|element| element * self)
     }
 }
 
@@ -97,37 +93,46 @@ impl ops::Mul<NumType> for &Vector
 {
     type Output = Vector;
     fn mul(self, other: NumType) -> Vector {
-        let data = self.data.iter()
-        .map(|&element| element * other).collect();
-
-        Vector {
-            data,
-            ..*self
-        }
+        self.map(|element| element * other)
     }
 }
 
+// 行優先で1本のVecに並べて持つ
+#[derive(Clone, Debug, PartialEq)]
 pub struct Matrix {
     rows:  usize,
     cols:  usize,
-    data:   Vec<Vec<NumType>>,
+    data:   Vec<NumType>,
 }
 
 impl Matrix {
     pub fn new(data: Vec<Vec<NumType>>) -> Self {
-        assert_eq!(
+        assert_ne!(
             data.len(), 0,
             "Can't create new matrix whose length is 0",
         );
-        Matrix {
-            rows: data.len(),
-            cols: data[0].len(),
-            data: data,
-        }
+        let rows: usize = data.len();
+        let cols: usize = data[0].len();
+        assert!(
+            data.iter().all(|row| row.len() == cols),
+            "Can't create new matrix from rows of different lengths",
+        );
+        Matrix::from_flat(rows, cols, data.into_iter().flatten().collect())
     }
 
-    pub fn zeros(rows: usize, cols: usize) -> Self {
-        let data = vec![vec![NumType::default(); cols]; rows];
+    pub fn from_flat(rows: usize, cols: usize, data: Vec<NumType>) -> Self {
+        assert_ne!(
+            rows, 0,
+            "Can't create new matrix whose length is 0",
+        );
+        assert_ne!(
+            cols, 0,
+            "Can't create new matrix whose width is 0",
+        );
+        assert_eq!(
+            rows * cols, data.len(),
+            "Matrix data length does not match its dimensions",
+        );
         Matrix {
             rows,
             cols,
@@ -135,18 +140,94 @@ impl Matrix {
         }
     }
 
+    pub fn zeros(rows: usize, cols: usize) -> Self {
+        Matrix::from_flat(rows, cols, vec![NumType::default(); rows * cols])
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn as_slice(&self) -> &[NumType] {
+        &self.data
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [NumType] {
+        &mut self.data
+    }
+
+    pub fn row(&self, row: usize) -> &[NumType] {
+        &self.data[row * self.cols..(row + 1) * self.cols]
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> NumType {
+        self.data[row * self.cols + col]
+    }
+
+    pub fn set(&mut self, row: usize, col: usize, value: NumType) {
+        self.data[row * self.cols + col] = value;
+    }
+
+    pub fn map<F: Fn(NumType) -> NumType>(&self, f: F) -> Self {
+        Matrix {
+            data: self.data.iter().map(|&element| f(element)).collect(),
+            ..*self
+        }
+    }
+
+    // 要素ごとの積
+    pub fn hadamard(&self, other: &Matrix) -> Self {
+        self.zip_with(other, |a, b| a * b)
+    }
+
+    fn zip_with<F: Fn(NumType, NumType) -> NumType>(&self, other: &Matrix, f: F) -> Self {
+        assert_eq!(
+            (self.rows, self.cols), (other.rows, other.cols),
+            "Matrix dimensions do not match"
+        );
+        Matrix {
+            data: self.data.iter().zip(&other.data)
+                .map(|(&a, &b)| f(a, b)).collect(),
+            ..*self
+        }
+    }
+
+    // 各列にvectorを足す. バッチを列に並べたときのバイアスの加算に使う
+    pub fn add_broadcast(&self, vector: &Vector) -> Self {
+        assert_eq!(
+            self.rows, vector.rows,
+            "Vector length does not match matrix rows for broadcasting"
+        );
+        let mut res: Matrix = self.clone();
+        for (row, &b) in res.data.chunks_mut(self.cols).zip(&vector.data) {
+            row.iter_mut().for_each(|element| *element += b);
+        }
+        res
+    }
+
+    // 行列とベクトルの積. 推論で使うので結果のVectorだけを確保する
+    pub fn mul_vec(&self, vector: &Vector) -> Vector {
+        assert_eq!(
+            self.cols, vector.rows,
+            "Matrix and vector dimensions do not match for multiplication"
+        );
+        Vector::new(self.data.chunks(self.cols)
+            .map(|row| row.iter().zip(&vector.data).map(|(a, b)| a * b).sum())
+            .collect())
+    }
+
     pub fn t(&self) -> Self {
-        let mut data = vec![vec![NumType::default(); self.rows]; self.cols];
+        let mut res: Matrix = Matrix::zeros(self.cols, self.rows);
         for i in 0..self.rows {
             for j in 0..self.cols {
-                data[j][i] = self.data[i][j];
+                res.data[j * self.rows + i] = self.data[i * self.cols + j];
             }
         }
-        Matrix {
-            rows: self.cols,
-            cols: self.rows,
-            data,
-        }
+        res
     }
 
     pub fn mat2vec(self) -> Vector {
@@ -154,7 +235,7 @@ impl Matrix {
             self.cols, 1,
             "Can't convert to Vector: cols is not 1",
         );
-        Vector::new(self.data.iter().flat_map(|row| row.iter()).cloned().collect())
+        Vector::new(self.data)
     }
 }
 
@@ -163,27 +244,16 @@ impl ops::Add for &Matrix
     type Output = Matrix;
 
     fn add(self, other: &Matrix) -> Matrix {
-        assert_eq!(
-            self.rows, other.rows,
-            "Matrix dimensions do not match for addition (rows)"
-        );
-        assert_eq!(
-            self.cols, other.cols,
-            "Matrix dimensions do not match for addition (cols)"
-        );
-
-        let mut data = vec![vec![Default::default(); self.cols]; self.rows];
+        self.zip_with(other, |a, b| a + b)
+    }
+}
 
-        for i in 0..self.rows {
-            for j in 0..self.cols {
-                data[i][j] = self.data[i][j] + other.data[i][j];
-            }
-        }
+impl ops::Add<&Vector> for &Matrix
+{
+    type Output = Matrix;
 
-        Matrix {
-            data: data,
-            ..*self
-        }
+    fn add(self, other: &Vector) -> Matrix {
+        self.add_broadcast(other)
     }
 }
 
@@ -192,27 +262,7 @@ impl ops::Sub for &Matrix
     type Output = Matrix;
 
     fn sub(self, other: &Matrix) -> Matrix {
-        assert_eq!(
-            self.rows, other.rows,
-            "Matrix dimensions do not match for subtraction (rows)"
-        );
-        assert_eq!(
-            self.cols, other.cols,
-            "Matrix dimensions do not match for subtraction (cols)"
-        );
-
-        let mut data = vec![vec![Default::default(); self.cols]; self.rows];
-
-        for i in 0..self.rows {
-            for j in 0..self.cols {
-                data[i][j] = self.data[i][j] - other.data[i][j];
-            }
-        }
-
-        Matrix {
-            data: data,
-            ..*self
-        }
+        self.zip_with(other, |a, b| a - b)
     }
 }
 
@@ -224,20 +274,23 @@ impl ops::Mul<&Matrix> for &Matrix
             self.cols, other.rows,
             "Matrix dimensions do not match for multiplication"
         );
-        let mut data = vec![vec![Default::default(); self.rows]; other.cols];
-        for k in 0..self.cols {
-            for i in 0..self.rows {
-                for j in 0..other.cols {
-                    data[i][j] += self.data[i][k] * other.data[k][j];
+        let mut res: Matrix = Matrix::zeros(self.rows, other.cols);
+        for (res_row, self_row) in res.data.chunks_mut(other.cols).zip(self.data.chunks(self.cols)) {
+            for (&a, other_row) in self_row.iter().zip(other.data.chunks(other.cols)) {
+                for (element, &b) in res_row.iter_mut().zip(other_row) {
+                    *element += a * b;
                 }
             }
         }
+        res
+    }
+}
 
-        Matrix {
-            rows: self.rows,
-            cols: other.cols,
-            data: data,
-        }
+impl ops::Mul<&Vector> for &Matrix
+{
+    type Output = Vector;
+    fn mul(self, other: &Vector) -> Vector {
+        self.mul_vec(other)
     }
 }
 
@@ -245,37 +298,21 @@ impl ops::Mul<NumType> for &Matrix
 {
     type Output = Matrix;
     fn mul(self, other: NumType) -> Matrix {
-        let data = self.data.iter()
-        .map(|row| row.iter().map(|&element| element * other).collect())
-        .collect();
-
-        Matrix {
-            data,
-            ..*self
-        }
+        self.map(|element| element * other)
     }
 }
 
 impl ops::Mul<&Matrix> for NumType
-where
-    NumType: num_traits::Num,
 {
     type Output = Matrix;
     fn mul(self, other: &Matrix) -> Matrix {
-        let data = other.data.iter()
-        .map(|row| row.iter().map(|&element| element * self).collect())
-        .collect();
-
-        Matrix {
-            data,
-            ..*other
-        }
+        other.map(|element| element * self)
     }
 }
 
 impl fmt::Display for Matrix {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        for row in &self.data {
+        for row in self.data.chunks(self.cols) {
             for element in row {
                 write!(f, "{} ", element)?;
             }
@@ -284,3 +321,109 @@ impl fmt::Display for Matrix {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Matrix {
+        Matrix::new(vec![vec![1., 2., 3.], vec![4., 5., 6.]])
+    }
+
+    #[test]
+    fn vector_ops() {
+        let a: Vector = Vector::new(vec![1., 2., 3.]);
+        let b: Vector = Vector::new(vec![4., 6., 8.]);
+        assert_eq!((&a + &b).data, vec![5., 8., 11.]);
+        assert_eq!((&b - &a).data, vec![3., 4., 5.]);
+        assert_eq!((&a * 2.).data, vec![2., 4., 6.]);
+        assert_eq!((2. * &a).data, vec![2., 4., 6.]);
+        assert_eq!(a.hadamard(&b).data, vec![4., 12., 24.]);
+        assert_eq!(a.dot(&b), 40.);
+        assert_eq!(a.map(|x| x - 1.).data, vec![0., 1., 2.]);
+    }
+
+    #[test]
+    fn new_keeps_shape_and_layout() {
+        let m: Matrix = sample();
+        assert_eq!((m.rows(), m.cols()), (2, 3));
+        assert_eq!(m.as_slice(), &[1., 2., 3., 4., 5., 6.]);
+        assert_eq!(m.row(1), &[4., 5., 6.]);
+        assert_eq!(m.get(0, 2), 3.);
+    }
+
+    #[test]
+    #[should_panic]
+    fn new_rejects_empty() {
+        Matrix::new(vec![]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn new_rejects_empty_rows() {
+        Matrix::new(vec![vec![]]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn from_flat_rejects_zero_cols() {
+        Matrix::from_flat(2, 0, vec![]);
+    }
+
+    // 転置すると列数0になるので, 行数0も作れない
+    #[test]
+    #[should_panic]
+    fn zeros_rejects_zero_rows() {
+        Matrix::zeros(0, 3);
+    }
+
+    #[test]
+    #[should_panic]
+    fn new_rejects_ragged_rows() {
+        Matrix::new(vec![vec![1., 2.], vec![3.]]);
+    }
+
+    #[test]
+    fn elementwise_ops() {
+        let a: Matrix = sample();
+        let b: Matrix = Matrix::new(vec![vec![6., 5., 4.], vec![3., 2., 1.]]);
+        assert_eq!((&a + &b).as_slice(), &[7.; 6]);
+        assert_eq!((&a - &b).as_slice(), &[-5., -3., -1., 1., 3., 5.]);
+        assert_eq!(a.hadamard(&b).as_slice(), &[6., 10., 12., 12., 10., 6.]);
+        assert_eq!((&a * 0.5).as_slice(), &[0.5, 1., 1.5, 2., 2.5, 3.]);
+        assert_eq!((0.5 * &a), &a * 0.5);
+    }
+
+    #[test]
+    fn matrix_product_of_non_square() {
+        let a: Matrix = sample();
+        let b: Matrix = Matrix::new(vec![vec![1., 0.], vec![0., 1.], vec![1., 1.]]);
+        let c: Matrix = &a * &b;
+        assert_eq!((c.rows(), c.cols()), (2, 2));
+        assert_eq!(c.as_slice(), &[4., 5., 10., 11.]);
+        assert_eq!((&b * &a).as_slice(), &[1., 2., 3., 4., 5., 6., 5., 7., 9.]);
+    }
+
+    #[test]
+    fn matrix_vector_product() {
+        let a: Matrix = sample();
+        let v: Vector = Vector::new(vec![1., 0., -1.]);
+        assert_eq!((&a * &v).data, vec![-2., -2.]);
+        assert_eq!((&a * &v.clone().vec2mat()).mat2vec(), a.mul_vec(&v));
+    }
+
+    #[test]
+    fn broadcasting_add() {
+        let a: Matrix = sample();
+        let bias: Vector = Vector::new(vec![10., 20.]);
+        assert_eq!((&a + &bias).as_slice(), &[11., 12., 13., 24., 25., 26.]);
+    }
+
+    #[test]
+    fn transpose() {
+        let t: Matrix = sample().t();
+        assert_eq!((t.rows(), t.cols()), (3, 2));
+        assert_eq!(t.as_slice(), &[1., 4., 2., 5., 3., 6.]);
+        assert_eq!(t.t(), sample());
+    }
+}
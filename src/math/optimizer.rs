@@ -0,0 +1,162 @@
+use super::matrix::{Matrix, NumType};
+
+// paramsとgradsは同じ順, 同じ形で渡す. 状態を持つものは最初のstepで形に合わせて初期化する
+pub trait Optimizer {
+    fn step(&mut self, params: &mut [Matrix], grads: &[Matrix], learning_rate: NumType);
+}
+
+fn zeros_like(params: &[Matrix]) -> Vec<Matrix> {
+    params.iter().map(|param| Matrix::zeros(param.rows(), param.cols())).collect()
+}
+
+pub struct Sgd;
+
+impl Optimizer for Sgd {
+    fn step(&mut self, params: &mut [Matrix], grads: &[Matrix], learning_rate: NumType) {
+        for (param, grad) in params.iter_mut().zip(grads) {
+            for (p, g) in param.as_mut_slice().iter_mut().zip(grad.as_slice()) {
+                *p -= learning_rate * g;
+            }
+        }
+    }
+}
+
+pub struct Momentum {
+    pub momentum:   NumType,
+    velocity:   Vec<Matrix>,
+}
+
+impl Momentum {
+    pub fn new(momentum: NumType) -> Momentum {
+        Momentum {
+            momentum,
+            velocity:   Vec::new(),
+        }
+    }
+}
+
+impl Optimizer for Momentum {
+    fn step(&mut self, params: &mut [Matrix], grads: &[Matrix], learning_rate: NumType) {
+        if self.velocity.len() != params.len() {
+            self.velocity = zeros_like(params);
+        }
+        for ((param, grad), velocity) in params.iter_mut().zip(grads).zip(self.velocity.iter_mut()) {
+            for ((p, g), v) in param.as_mut_slice().iter_mut().zip(grad.as_slice()).zip(velocity.as_mut_slice()) {
+                *v = self.momentum * *v - learning_rate * g;
+                *p += *v;
+            }
+        }
+    }
+}
+
+pub struct Adam {
+    pub beta1:  NumType,
+    pub beta2:  NumType,
+    pub epsilon:    NumType,
+    m:  Vec<Matrix>,
+    v:  Vec<Matrix>,
+    t:  i32,
+}
+
+impl Adam {
+    pub fn new(beta1: NumType, beta2: NumType, epsilon: NumType) -> Adam {
+        Adam {
+            beta1,
+            beta2,
+            epsilon,
+            m:  Vec::new(),
+            v:  Vec::new(),
+            t:  0,
+        }
+    }
+}
+
+impl Default for Adam {
+    fn default() -> Adam {
+        Adam::new(0.9, 0.999, 1e-8)
+    }
+}
+
+impl Optimizer for Adam {
+    fn step(&mut self, params: &mut [Matrix], grads: &[Matrix], learning_rate: NumType) {
+        if self.m.len() != params.len() {
+            self.m = zeros_like(params);
+            self.v = zeros_like(params);
+            self.t = 0;
+        }
+        self.t += 1;
+        let correction1: NumType = 1. - self.beta1.powi(self.t);
+        let correction2: NumType = 1. - self.beta2.powi(self.t);
+
+        for (((param, grad), m), v) in params.iter_mut().zip(grads).zip(self.m.iter_mut()).zip(self.v.iter_mut()) {
+            let moments = m.as_mut_slice().iter_mut().zip(v.as_mut_slice());
+            for ((p, &g), (m, v)) in param.as_mut_slice().iter_mut().zip(grad.as_slice()).zip(moments) {
+                *m = self.beta1 * *m + (1. - self.beta1) * g;
+                *v = self.beta2 * *v + (1. - self.beta2) * g * g;
+                *p -= learning_rate * (*m / correction1) / ((*v / correction2).sqrt() + self.epsilon);
+            }
+        }
+    }
+}
+
+// epochは0始まり
+#[derive(Debug, Clone, Copy)]
+pub enum LearningRate {
+    Constant(NumType),
+    StepDecay { initial: NumType, factor: NumType, step_epochs: usize },  // step_epochsごとにfactor倍
+    Exponential { initial: NumType, decay: NumType },                     // 毎epoch decay倍
+    Cosine { initial: NumType, min: NumType, total_epochs: usize },
+}
+
+impl LearningRate {
+    pub fn rate(&self, epoch: usize) -> NumType {
+        match *self {
+            LearningRate::Constant(rate) => rate,
+            LearningRate::StepDecay { initial, factor, step_epochs } => initial * factor.powi((epoch / step_epochs.max(1)) as i32),
+            LearningRate::Exponential { initial, decay } => initial * decay.powi(epoch as i32),
+            LearningRate::Cosine { initial, min, total_epochs } => {
+                let progress: NumType = (epoch as NumType / total_epochs.max(1) as NumType).min(1.);
+                min + (initial - min) * (1. + (std::f32::consts::PI * progress).cos()) / 2.
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // (x - 3)^2 + (y + 1)^2 を最小化する
+    fn minimize(optimizer: &mut dyn Optimizer, learning_rate: NumType, steps: usize) -> Matrix {
+        let target: Matrix = Matrix::new(vec![vec![3., -1.]]);
+        let mut params: Vec<Matrix> = vec![Matrix::zeros(1, 2)];
+        for _ in 0..steps {
+            let grads: Vec<Matrix> = vec![&(&params[0] - &target) * 2.];
+            optimizer.step(&mut params, &grads, learning_rate);
+        }
+        params.remove(0)
+    }
+
+    fn assert_near(param: &Matrix) {
+        assert!((param.get(0, 0) - 3.).abs() < 1e-2 && (param.get(0, 1) + 1.).abs() < 1e-2, "{}", param);
+    }
+
+    #[test]
+    fn optimizers_reach_minimum() {
+        assert_near(&minimize(&mut Sgd, 0.1, 200));
+        assert_near(&minimize(&mut Momentum::new(0.9), 0.02, 500));
+        assert_near(&minimize(&mut Adam::default(), 0.05, 2000));
+    }
+
+    #[test]
+    fn learning_rate_schedule() {
+        assert_eq!(LearningRate::Constant(0.1).rate(10), 0.1);
+        let step: LearningRate = LearningRate::StepDecay { initial: 1., factor: 0.5, step_epochs: 2 };
+        assert_eq!([step.rate(0), step.rate(1), step.rate(2), step.rate(5)], [1., 1., 0.5, 0.25]);
+        assert_eq!(LearningRate::Exponential { initial: 1., decay: 0.5 }.rate(3), 0.125);
+        let cosine: LearningRate = LearningRate::Cosine { initial: 1., min: 0., total_epochs: 10 };
+        assert_eq!(cosine.rate(0), 1.);
+        assert!((cosine.rate(5) - 0.5).abs() < 1e-6);
+        assert!(cosine.rate(10).abs() < 1e-6);
+    }
+}
@@ -1,76 +1,293 @@
-use std::ops;
-use num_traits;
+use std::{cell::RefCell, ops};
 
-pub struct Scalar<T>
-where T: num_traits::Num,
-{
-    data:   T,
+use super::{functions::{Activation, Function, Identity, ReLU, Sigmoid, Tanh}, matrix::{Matrix, NumType, Vector}};
+
+// 各変数がどの演算で作られたか. 引数は計算グラフ内の番号
+enum Operation {
+    Leaf,
+    Add(usize, usize),
+    AddBroadcast(usize, usize), // 2つ目は列ベクトル(rows x 1)で, 各列に足す
+    Sub(usize, usize),
+    Mul(usize, usize),      // 要素ごとの積
+    MatMul(usize, usize),
+    Scale(usize, NumType),
+    Activation(usize, fn(NumType, NumType) -> NumType),    // Function::derivative
+    Sum(usize),
+}
+
+struct Node {
+    value:  Matrix,
+    operation:  Operation,
 }
 
-pub struct Vector<T>
-where T: num_traits::Num,
-{
-    data: Vec<Scalar<T>>
+// reverse modeの自動微分のための計算グラフ
+// 変数は作られた順に並ぶので, backwardでは逆順にたどるだけで勾配が求まる
+pub struct Graph {
+    nodes:  RefCell<Vec<Node>>,
+    grads:  RefCell<Vec<Matrix>>,
 }
 
-pub struct Matrix<T>
-where T: num_traits::Num,
-{
-    rows:   usize,
-    cols:   usize,
-    data:   Vec<Vector<T>>,
+#[derive(Clone, Copy)]
+pub struct Variable<'a> {
+    graph:  &'a Graph,
+    index:  usize,
 }
 
+impl Graph {
+    pub fn new() -> Graph {
+        Graph {
+            nodes:  RefCell::new(Vec::new()),
+            grads:  RefCell::new(Vec::new()),
+        }
+    }
+
+    // 入力や学習するパラメータを追加する
+    pub fn variable(&self, value: Matrix) -> Variable<'_> {
+        self.push(value, Operation::Leaf)
+    }
+
+    fn push(&self, value: Matrix, operation: Operation) -> Variable<'_> {
+        let mut nodes = self.nodes.borrow_mut();
+        nodes.push(Node {
+            value,
+            operation,
+        });
+        Variable {
+            graph:  self,
+            index:  nodes.len() - 1,
+        }
+    }
 
-impl<T> ops::Add for &Scalar<T>
-where T: num_traits::Num,
-{
-    type Output = Scalar<T>;
+    fn value(&self, index: usize) -> Matrix {
+        self.nodes.borrow()[index].value.clone()
+    }
 
-    fn add(self, other: &Scalar<T>) -> Scalar<T> {
-        Scalar {
-            data: self.data + other.data,
+    fn backward(&self, index: usize) {
+        let nodes = self.nodes.borrow();
+        assert_eq!(
+            (nodes[index].value.rows(), nodes[index].value.cols()), (1, 1),
+            "backward() needs a scalar variable"
+        );
+        let mut grads = self.grads.borrow_mut();
+        *grads = nodes.iter().map(|node| Matrix::zeros(node.value.rows(), node.value.cols())).collect();
+        grads[index] = Matrix::new(vec![vec![1.]]);
+
+        for i in (0..=index).rev() {
+            let grad: Matrix = grads[i].clone();
+            match nodes[i].operation {
+                Operation::Leaf => {}
+                Operation::Add(a, b) => {
+                    grads[a] = &grads[a] + &grad;
+                    grads[b] = &grads[b] + &grad;
+                }
+                Operation::AddBroadcast(a, b) => {
+                    grads[a] = &grads[a] + &grad;
+                    let row_sums: Vec<NumType> = (0..grad.rows()).map(|row| grad.row(row).iter().sum()).collect();
+                    grads[b] = &grads[b] + &Vector::new(row_sums).vec2mat();
+                }
+                Operation::Sub(a, b) => {
+                    grads[a] = &grads[a] + &grad;
+                    grads[b] = &grads[b] - &grad;
+                }
+                Operation::Mul(a, b) => {
+                    grads[a] = &grads[a] + &grad.hadamard(&nodes[b].value);
+                    grads[b] = &grads[b] + &grad.hadamard(&nodes[a].value);
+                }
+                Operation::MatMul(a, b) => {
+                    grads[a] = &grads[a] + &(&grad * &nodes[b].value.t());
+                    grads[b] = &grads[b] + &(&nodes[a].value.t() * &grad);
+                }
+                Operation::Scale(a, c) => {
+                    grads[a] = &grads[a] + &(&grad * c);
+                }
+                Operation::Activation(a, derivative) => {
+                    let input: &Matrix = &nodes[a].value;
+                    let output: &Matrix = &nodes[i].value;
+                    let mut local: Matrix = Matrix::zeros(input.rows(), input.cols());
+                    for row in 0..input.rows() {
+                        for col in 0..input.cols() {
+                            local.set(row, col, derivative(input.get(row, col), output.get(row, col)));
+                        }
+                    }
+                    grads[a] = &grads[a] + &grad.hadamard(&local);
+                }
+                Operation::Sum(a) => {
+                    let g: NumType = grad.get(0, 0);
+                    grads[a] = grads[a].map(|element| element + g);
+                }
+            }
         }
     }
 }
 
-impl<T> ops::Mul for &Scalar<T>
-where T: num_traits::Num,
-{
-    type Output = Scalar<T>;
+impl Default for Graph {
+    fn default() -> Graph {
+        Graph::new()
+    }
+}
+
+impl<'a> Variable<'a> {
+    pub fn value(&self) -> Matrix {
+        self.graph.value(self.index)
+    }
+
+    // 直前のbackward()で求めた勾配. まだ求めていなければ0
+    pub fn grad(&self) -> Matrix {
+        match self.graph.grads.borrow().get(self.index) {
+            Some(grad) => grad.clone(),
+            None => {
+                let value: Matrix = self.value();
+                Matrix::zeros(value.rows(), value.cols())
+            }
+        }
+    }
+
+    // 1x1の変数から, それを作るのに使った全変数の勾配を求める
+    pub fn backward(&self) {
+        self.graph.backward(self.index);
+    }
+
+    pub fn matmul(&self, other: Variable<'a>) -> Variable<'a> {
+        let value: Matrix = &self.value() * &other.value();
+        self.graph.push(value, Operation::MatMul(self.index, other.index))
+    }
+
+    pub fn apply<F: Function>(&self) -> Variable<'a> {
+        let value: Matrix = self.value().map(F::forward);
+        self.graph.push(value, Operation::Activation(self.index, F::derivative))
+    }
+
+    // otherは列ベクトル(rows x 1). バッチを列に並べたときのバイアスの加算に使う
+    pub fn add_broadcast(&self, other: Variable<'a>) -> Variable<'a> {
+        let value: Matrix = self.value().add_broadcast(&other.value().mat2vec());
+        self.graph.push(value, Operation::AddBroadcast(self.index, other.index))
+    }
 
-    fn mul(self, other: &Scalar<T>) -> Scalar<T> {
-        Scalar {
-            data: self.data * other.data,
+    pub fn apply_activation(&self, activation: Activation) -> Variable<'a> {
+        match activation {
+            Activation::Identity => self.apply::<Identity>(),
+            Activation::ReLU => self.apply::<ReLU>(),
+            Activation::Tanh => self.apply::<Tanh>(),
+            Activation::Sigmoid => self.apply::<Sigmoid>(),
         }
     }
+
+    pub fn sum(&self) -> Variable<'a> {
+        let value: Matrix = self.value();
+        let mut sum: NumType = 0.;
+        for row in 0..value.rows() {
+            for col in 0..value.cols() {
+                sum += value.get(row, col);
+            }
+        }
+        self.graph.push(Matrix::new(vec![vec![sum]]), Operation::Sum(self.index))
+    }
 }
 
-trait Function<T>
-{
-    fn forward(input: T) -> T;
+impl<'a> ops::Add for Variable<'a> {
+    type Output = Variable<'a>;
+
+    fn add(self, other: Variable<'a>) -> Variable<'a> {
+        let value: Matrix = &self.value() + &other.value();
+        self.graph.push(value, Operation::Add(self.index, other.index))
+    }
 }
 
-impl<F, T> Function<Vector<T>> for F
-where T: num_traits::Num + std::cmp::PartialOrd<i32>,
-{
-    fn forward(input: Vector<T>) -> Vector<T> {
-        input.data.iter()
-        .map(|x| F::forward(*x)).collect()
+impl<'a> ops::Sub for Variable<'a> {
+    type Output = Variable<'a>;
+
+    fn sub(self, other: Variable<'a>) -> Variable<'a> {
+        let value: Matrix = &self.value() - &other.value();
+        self.graph.push(value, Operation::Sub(self.index, other.index))
     }
 }
 
-struct ReLU;
+// 要素ごとの積. 行列積はmatmul
+impl<'a> ops::Mul for Variable<'a> {
+    type Output = Variable<'a>;
 
-impl<T> Function<Scalar<T>> for ReLU
-where T: num_traits::Num + std::cmp::PartialOrd<i32>,
-{
-    fn forward(input: Scalar<T>) -> Scalar<T> {
-        if input.data > 0 {
-            input
-        } else {
-            Scalar { data: num_traits::zero() }
-        }
+    fn mul(self, other: Variable<'a>) -> Variable<'a> {
+        let value: Matrix = self.value().hadamard(&other.value());
+        self.graph.push(value, Operation::Mul(self.index, other.index))
     }
 }
 
+impl<'a> ops::Mul<NumType> for Variable<'a> {
+    type Output = Variable<'a>;
+
+    fn mul(self, other: NumType) -> Variable<'a> {
+        let value: Matrix = &self.value() * other;
+        self.graph.push(value, Operation::Scale(self.index, other))
+    }
+}
+
+// functions::mseと同じく二乗誤差の和
+pub fn mse<'a>(target: Variable<'a>, x: Variable<'a>) -> Variable<'a> {
+    let diff: Variable<'a> = x - target;
+    (diff * diff).sum()
+}
+
+// パラメータ1つをvalueの前後にずらしたときの損失の変化(数値微分)がanalyticと一致するか調べる
+// lossはパラメータの値を受け取って損失を返す. 学習モデルの勾配のテストでも使う
+#[cfg(test)]
+pub(crate) fn check_grad_at(mut loss: impl FnMut(NumType) -> NumType, value: NumType, analytic: NumType) {
+    let eps: NumType = 1e-2;
+    let numerical: NumType = (loss(value + eps) - loss(value - eps)) / (2. * eps);
+    assert!((numerical - analytic).abs() < 1e-2, "numerical {} analytic {}", numerical, analytic);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::functions::{Sigmoid, Tanh};
+
+    // 各パラメータを少しずらしたときの損失の変化と勾配を比べる
+    fn check_grad(loss: impl for<'a> Fn(&'a Graph, Variable<'a>) -> Variable<'a>, param: Matrix) {
+        let graph: Graph = Graph::new();
+        let x: Variable = graph.variable(param.clone());
+        loss(&graph, x).backward();
+        let grad: Matrix = x.grad();
+
+        for row in 0..param.rows() {
+            for col in 0..param.cols() {
+                check_grad_at(|value| {
+                    let mut p: Matrix = param.clone();
+                    p.set(row, col, value);
+                    let graph: Graph = Graph::new();
+                    let x: Variable = graph.variable(p);
+                    loss(&graph, x).value().get(0, 0)
+                }, param.get(row, col), grad.get(row, col));
+            }
+        }
+    }
+
+    #[test]
+    fn matmul_and_activation_grad() {
+        let w: Matrix = Matrix::new(vec![vec![0.5, -0.3, 0.2], vec![0.1, 0.4, -0.6]]);
+        check_grad(|graph, w| {
+            let x: Variable = graph.variable(Matrix::new(vec![vec![1.], vec![-2.], vec![0.5]]));
+            let target: Variable = graph.variable(Matrix::new(vec![vec![0.3], vec![-0.1]]));
+            let h: Variable = w.matmul(x).apply::<Tanh>();
+            mse(target, h * 2.)
+        }, w);
+    }
+
+    #[test]
+    fn broadcast_grad() {
+        let b: Matrix = Matrix::new(vec![vec![0.1], vec![-0.4]]);
+        check_grad(|graph, b| {
+            let x: Variable = graph.variable(Matrix::new(vec![vec![1., 2., 3.], vec![-1., 0., 1.]]));
+            x.add_broadcast(b).apply_activation(Activation::ReLU).sum()
+        }, b);
+    }
+
+    #[test]
+    fn elementwise_grad() {
+        let a: Matrix = Matrix::new(vec![vec![0.2, -0.7], vec![1.5, 0.3]]);
+        check_grad(|graph, a| {
+            let b: Variable = graph.variable(Matrix::new(vec![vec![1., 2.], vec![-1., 0.5]]));
+            let y: Variable = (a * b - a + b).apply::<Sigmoid>() * a;
+            y.sum()
+        }, a);
+    }
+}
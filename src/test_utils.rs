@@ -0,0 +1,38 @@
+// テストで共通に使う局面の生成など
+use std::{env, fs, path::Path, process};
+
+use rand::{rngs::StdRng, seq::SliceRandom};
+
+use crate::components::{action::Action, bitboard::BitBoard, board::Board, constants::{TOP_BIT, BOARD_SIZE}, enums::BoardStatus, state::State};
+
+// 合法手から一様に1つ選ぶ. 合法手がなければNone
+pub(crate) fn random_action(state: &State, rng: &mut StdRng) -> Option<Action> {
+    let legal_actions: BitBoard = state.legal_actions_bitboard();
+    let bits: Vec<BitBoard> = (0..BOARD_SIZE).map(|i| TOP_BIT >> i).filter(|bit| legal_actions & bit != 0).collect();
+    bits.choose(rng).map(|&bit| Action::action_from_bitboard(bit))
+}
+
+// 初期局面からランダムにmove_num手打った局面. パスは手数に数えず, 終局したらそこで止める
+pub(crate) fn random_board(move_num: usize, rng: &mut StdRng) -> Board {
+    let mut board: Board = Board::init();
+    let mut played: usize = 0;
+    while played < move_num {
+        match board.status() {
+            BoardStatus::Finished => break,
+            BoardStatus::Pass => { board = board.play_pass(); },
+            BoardStatus::Usual => {
+                board = board.play_onestep(random_action(&board.state, rng).unwrap());
+                played += 1;
+            },
+        }
+    }
+    board
+}
+
+// nameごとの一時ファイルのパスをfに渡し, 終わったらファイルを消す
+pub(crate) fn with_temp_file<T>(name: &str, f: impl FnOnce(&Path) -> T) -> T {
+    let path = env::temp_dir().join(format!("othello_ai_{}_{}.txt", name, process::id()));
+    let result: T = f(&path);
+    let _ = fs::remove_file(&path);
+    result
+}